@@ -0,0 +1,300 @@
+//! A JSON version of the board API, for scripts and overlays. It goes through
+//! the same events as the HTML endpoints, so changes made here are journaled,
+//! undoable and pushed to every open browser.
+
+use core::net::SocketAddr;
+
+use axum::{
+  Json, Router,
+  extract::{ConnectInfo, FromRequest, FromRequestParts, Request, rejection},
+  http::{StatusCode, request::Parts},
+  response::{IntoResponse, Response},
+  routing::{get, post},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use dv_tracker::{
+  Event, InvalidOrder, Order, OrderEdit, PlanError, TransitionError, unix_time,
+};
+
+use crate::{
+  AppState, CurrentBoard, GuidPath, MovePath, PlanRequest, check_events,
+  error::AppError,
+  extract::{Path, Query},
+};
+
+pub fn router() -> Router<AppState> {
+  Router::new()
+    .route("/orders", get(list).post(create))
+    .route("/orders/{guid}", get(show).patch(update).delete(remove))
+    .route("/orders/{guid}/duplicate", post(duplicate))
+    .route("/orders/{guid}/move/{direction}", post(move_order))
+    .route("/plan", get(plan))
+    .fallback(async || error(StatusCode::NOT_FOUND, "no such endpoint"))
+    .method_not_allowed_fallback(async || {
+      error(StatusCode::METHOD_NOT_ALLOWED, "method not allowed")
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct ApiError {
+  error: &'static str,
+  /// Structured details, for errors that have them.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  details: Option<InvalidOrder>,
+  /// Why the change isn't allowed, for errors that say.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  reason: Option<String>,
+}
+
+fn error(status: StatusCode, error: &'static str) -> Response {
+  (
+    status,
+    Json(ApiError {
+      error,
+      details: None,
+      reason: None,
+    }),
+  )
+    .into_response()
+}
+
+/// Lists the valid yards or tracks alongside the message, so a client can
+/// suggest a fix.
+fn invalid(details: InvalidOrder) -> Response {
+  (
+    StatusCode::UNPROCESSABLE_ENTITY,
+    Json(ApiError {
+      error: "invalid order",
+      details: Some(details),
+      reason: None,
+    }),
+  )
+    .into_response()
+}
+
+/// A change the order's status doesn't allow, like checking off a dropoff
+/// before its pickups.
+fn transition(error: TransitionError) -> Response {
+  (
+    StatusCode::CONFLICT,
+    Json(ApiError {
+      error: "not allowed in the order's status",
+      details: None,
+      reason: Some(error.to_string()),
+    }),
+  )
+    .into_response()
+}
+
+fn not_found() -> Response {
+  error(StatusCode::NOT_FOUND, "no such order")
+}
+
+/// The JSON version of an error shared with the HTML endpoints.
+fn app_error(e: AppError) -> Response {
+  match e {
+    AppError::NoSuchBoard => error(StatusCode::NOT_FOUND, "no such board"),
+    AppError::NoSuchOrder => not_found(),
+    AppError::Invalid(details) => invalid(details),
+    AppError::Transition(e) => transition(e),
+    AppError::Rejected(status, reason) => (
+      status,
+      Json(ApiError {
+        error: "bad request",
+        details: None,
+        reason: Some(reason),
+      }),
+    )
+      .into_response(),
+    e => (
+      e.status(),
+      Json(ApiError {
+        error: "request failed",
+        details: None,
+        reason: Some(e.to_string()),
+      }),
+    )
+      .into_response(),
+  }
+}
+
+/// Wraps an extractor shared with the HTML endpoints, like [`CurrentBoard`]
+/// or [`Path`], so that it fails with JSON instead of the error banner.
+struct Api<T>(T);
+
+impl<T, S> FromRequestParts<S> for Api<T>
+where
+  T: FromRequestParts<S, Rejection = AppError>,
+  S: Send + Sync,
+{
+  type Rejection = Response;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    state: &S,
+  ) -> Result<Self, Response> {
+    T::from_request_parts(parts, state)
+      .await
+      .map(Api)
+      .map_err(app_error)
+  }
+}
+
+/// A JSON request body, which fails with JSON too.
+struct Body<T>(T);
+
+impl<T, S> FromRequest<S> for Body<T>
+where
+  T: DeserializeOwned,
+  S: Send + Sync,
+{
+  type Rejection = Response;
+
+  async fn from_request(req: Request, state: &S) -> Result<Self, Response> {
+    Json::<T>::from_request(req, state)
+      .await
+      .map(|Json(value)| Body(value))
+      .map_err(|e: rejection::JsonRejection| {
+        app_error(AppError::Rejected(e.status(), e.body_text()))
+      })
+  }
+}
+
+/// Uses the same snake_case keys as [`Order`], and rejects any others so a
+/// mistyped key isn't silently ignored.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OrderPatch {
+  #[serde(flatten)]
+  edit: OrderEdit,
+  from_done: Option<bool>,
+  to_done: Option<bool>,
+}
+
+async fn list(Api(CurrentBoard(board)): Api<CurrentBoard>) -> Response {
+  let store = board.store.lock().await;
+
+  Json(&store.orders).into_response()
+}
+
+async fn create(
+  Api(CurrentBoard(board)): Api<CurrentBoard>,
+  ConnectInfo(who): ConnectInfo<SocketAddr>,
+  Body(mut order): Body<Order>,
+) -> Response {
+  if let Err(e) = order.validate() {
+    return invalid(e);
+  }
+  if let Err(e) = order.check_lifecycle() {
+    return transition(e);
+  }
+  if order.taken_at.is_some_and(|at| at > unix_time()) {
+    return error(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      "taken_at is in the future",
+    );
+  }
+  // Stamped again when it's applied, if the order is finished.
+  order.finished_at = None;
+  let mut store = board.store.lock().await;
+
+  board.commit(&mut store, who, Event::Add { order }).await;
+  (StatusCode::CREATED, Json(store.orders.last())).into_response()
+}
+
+async fn show(
+  Api(CurrentBoard(board)): Api<CurrentBoard>,
+  Api(Path(GuidPath { guid })): Api<Path<GuidPath>>,
+) -> Response {
+  let store = board.store.lock().await;
+
+  match store.get(guid) {
+    Some(order) => Json(order).into_response(),
+    None => not_found(),
+  }
+}
+
+async fn update(
+  Api(CurrentBoard(board)): Api<CurrentBoard>,
+  ConnectInfo(who): ConnectInfo<SocketAddr>,
+  Api(Path(GuidPath { guid })): Api<Path<GuidPath>>,
+  Body(patch): Body<OrderPatch>,
+) -> Response {
+  let mut store = board.store.lock().await;
+  let Some(order) = store.get(guid) else {
+    return not_found();
+  };
+  let events = Event::edits(guid, patch.edit, patch.from_done, patch.to_done);
+  if let Err(e) = check_events(order, &events) {
+    return app_error(e);
+  }
+
+  for event in events {
+    board.commit(&mut store, who, event).await;
+  }
+  Json(store.get(guid)).into_response()
+}
+
+async fn remove(
+  Api(CurrentBoard(board)): Api<CurrentBoard>,
+  ConnectInfo(who): ConnectInfo<SocketAddr>,
+  Api(Path(GuidPath { guid })): Api<Path<GuidPath>>,
+) -> Response {
+  let mut store = board.store.lock().await;
+  if store.get(guid).is_none() {
+    return not_found();
+  }
+
+  board.commit(&mut store, who, Event::Remove { guid }).await;
+  StatusCode::NO_CONTENT.into_response()
+}
+
+async fn duplicate(
+  Api(CurrentBoard(board)): Api<CurrentBoard>,
+  ConnectInfo(who): ConnectInfo<SocketAddr>,
+  Api(Path(GuidPath { guid })): Api<Path<GuidPath>>,
+) -> Response {
+  let mut store = board.store.lock().await;
+  let Some(pos) = store.orders.iter().position(|o| o.guid == guid) else {
+    return not_found();
+  };
+
+  // The copy is inserted right after the original.
+  board
+    .commit(&mut store, who, Event::Duplicate { guid })
+    .await;
+  (StatusCode::CREATED, Json(&store.orders[pos + 1])).into_response()
+}
+
+async fn move_order(
+  Api(CurrentBoard(board)): Api<CurrentBoard>,
+  ConnectInfo(who): ConnectInfo<SocketAddr>,
+  Api(Path(MovePath { guid, direction })): Api<Path<MovePath>>,
+) -> Response {
+  let mut store = board.store.lock().await;
+  if store.get(guid).is_none() {
+    return not_found();
+  }
+
+  board
+    .commit(&mut store, who, Event::Move { guid, direction })
+    .await;
+  Json(&store.orders).into_response()
+}
+
+async fn plan(
+  Api(CurrentBoard(board)): Api<CurrentBoard>,
+  Api(Query(req)): Api<Query<PlanRequest>>,
+) -> Response {
+  match board.plan(req.start()).await {
+    Ok(plan) => Json(plan).into_response(),
+    Err(PlanError::NoConnections) => error(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      "the map has no connections between stations",
+    ),
+    Err(PlanError::UnknownStart(_)) => {
+      error(StatusCode::UNPROCESSABLE_ENTITY, "unknown start station")
+    }
+  }
+}
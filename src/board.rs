@@ -0,0 +1,219 @@
+use core::net::SocketAddr;
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+  sync::{
+    Arc, Mutex, PoisonError,
+    atomic::{AtomicBool, Ordering},
+  },
+};
+
+use internment::Intern;
+use maud::{Markup, html};
+use tokio::sync::broadcast;
+
+use dv_tracker::{Entry, Event, Journal, OrderStore, Plan, PlanError, map};
+
+/// A named set of orders, saved to `<name>.json` and journaled to
+/// `<name>.jsonl` in the data directory.
+pub struct Board {
+  pub store: tokio::sync::Mutex<OrderStore>,
+  journal: Arc<Mutex<Journal>>,
+  data: Mutex<PathBuf>,
+  /// Set by [`Self::delete`], after which changes are no longer written.
+  deleted: AtomicBool,
+  /// The freshly rendered orders, sent after every change.
+  pub updates: broadcast::Sender<String>,
+}
+
+impl Board {
+  /// Opens a board, loading its data file and replaying any newer journal
+  /// entries on top. A board that doesn't exist yet starts out empty.
+  pub fn open(dir: &Path, name: &str) -> io::Result<Self> {
+    let data = dir.join(format!("{name}.json"));
+    let mut store = OrderStore::load(&data)?;
+    let (journal, entries) = Journal::open(dir.join(format!("{name}.jsonl")))?;
+
+    let snapshot_seq = store.seq();
+    for entry in &entries {
+      store.apply(entry);
+    }
+    tracing::info!(
+      "board {name:?}: loaded {} orders, replayed {} of {} journal entries",
+      store.orders.len(),
+      store.seq() - snapshot_seq,
+      entries.len(),
+    );
+    if store.seq() > snapshot_seq {
+      store.save(&data)?;
+    }
+
+    Ok(Self {
+      store: tokio::sync::Mutex::new(store),
+      journal: Arc::new(Mutex::new(journal)),
+      data: Mutex::new(data),
+      deleted: AtomicBool::new(false),
+      updates: broadcast::channel(16).0,
+    })
+  }
+
+  /// Applies an event to the store, journals and saves it, and pushes the
+  /// result to everyone watching the board's event stream.
+  ///
+  /// The store must be locked by the caller for the whole call, so that
+  /// journal order always matches the order events were applied in. The
+  /// writes happen on a blocking thread, so they don't hold up the runtime.
+  pub async fn commit(
+    &self,
+    store: &mut OrderStore,
+    who: SocketAddr,
+    event: Event,
+  ) {
+    let entry = Entry::new(store.seq() + 1, who.ip().to_string(), event);
+    store.apply(&entry);
+    // A request that got hold of the board before it was deleted would
+    // otherwise write its files back.
+    if self.deleted.load(Ordering::SeqCst) {
+      return;
+    }
+
+    let snapshot = store.snapshot();
+    let journal = Arc::clone(&self.journal);
+    let data = match self.data.lock() {
+      Ok(data) => data.clone(),
+      Err(e) => {
+        tracing::error!("failed to lock data path: {e}");
+        return;
+      }
+    };
+    let write = tokio::task::spawn_blocking(move || {
+      match journal.lock() {
+        Ok(mut journal) => {
+          if let Err(e) = journal.append(&entry) {
+            tracing::error!("failed to journal {entry:?}: {e}");
+          }
+        }
+        Err(e) => tracing::error!("failed to lock journal: {e}"),
+      }
+
+      if let Err(e) =
+        snapshot.and_then(|s| OrderStore::save_snapshot(&data, &s))
+      {
+        tracing::error!("failed to save orders to {data:?}: {e}");
+      }
+    });
+    if let Err(e) = write.await {
+      tracing::error!("failed to write the board: {e}");
+    }
+
+    // Nobody is listening, so there's no need to render anything.
+    if self.updates.receiver_count() > 0 {
+      // An error only means everyone stopped listening since.
+      let _ = self.updates.send(store.render().into_string());
+    }
+  }
+
+  /// Plans a route through the board's unfinished orders. The planning runs
+  /// on a copy of them, on a blocking thread, so a long board doesn't hold up
+  /// everyone else's changes while it's worked out.
+  pub async fn plan(
+    &self,
+    start: Option<Intern<String>>,
+  ) -> Result<Plan, PlanError> {
+    let orders = self.store.lock().await.orders.clone();
+    tokio::task::spawn_blocking(move || dv_tracker::plan(map(), &orders, start))
+      .await
+      .expect("the planner doesn't panic")
+  }
+
+  /// Moves the board's files over to a new name.
+  pub async fn rename(&self, dir: &Path, to: &str) -> io::Result<()> {
+    // Holding the store keeps anyone from committing halfway through.
+    let _store = self.store.lock().await;
+    let mut journal = self.journal.lock().map_err(poisoned)?;
+    let mut data = self.data.lock().map_err(poisoned)?;
+
+    let new_data = dir.join(format!("{to}.json"));
+    if data.exists() {
+      fs::rename(data.as_path(), &new_data)?;
+    }
+    *data = new_data;
+    journal.rename(dir.join(format!("{to}.jsonl")))
+  }
+
+  /// Removes the board's files, and stops any later change from writing them
+  /// again.
+  pub async fn delete(&self) -> io::Result<()> {
+    let _store = self.store.lock().await;
+    self.deleted.store(true, Ordering::SeqCst);
+    let journal = self.journal.lock().map_err(poisoned)?;
+    let data = self.data.lock().map_err(poisoned)?;
+
+    for path in [data.as_path(), journal.path()] {
+      match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+      }
+    }
+
+    Ok(())
+  }
+}
+
+fn poisoned<T>(_: PoisonError<T>) -> io::Error {
+  io::Error::other("board lock poisoned")
+}
+
+/// Board names end up in file names and URLs, so they are kept to letters,
+/// digits, `-` and `_`.
+pub fn is_valid_name(name: &str) -> bool {
+  (1..=32).contains(&name.len())
+    && name
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Lists the names of the boards saved in `dir`.
+pub fn saved_names(dir: &Path) -> io::Result<Vec<String>> {
+  let mut names = Vec::new();
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+    let is_board = matches!(
+      path.extension().and_then(|e| e.to_str()),
+      Some("json" | "jsonl")
+    );
+    if let Some(name) = path.file_stem().and_then(|s| s.to_str())
+      && is_board
+      && is_valid_name(name)
+      && !names.iter().any(|n| n == name)
+    {
+      names.push(name.to_string());
+    }
+  }
+
+  Ok(names)
+}
+
+pub fn render_list<'a>(
+  names: impl IntoIterator<Item = &'a String>,
+  error: Option<&str>,
+) -> Markup {
+  html!(
+    @if let Some(error) = error {
+      p.error { (error) }
+    }
+    ul {
+      @for name in names {
+        li {
+          a href={"/b/" (name) "/"} { (name) }
+          " "
+          form.inline hx-post={"/boards/" (name) "/rename"} hx-target="#boards" {
+            input name="name" value=(name) required;
+            button type="submit" { "Rename" }
+          }
+          button hx-delete={"/boards/" (name)} hx-target="#boards" hx-confirm={"Delete " (name) " and all of its orders?"} { "x" }
+        }
+      }
+    }
+  )
+}
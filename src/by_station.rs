@@ -0,0 +1,70 @@
+use internment::Intern;
+use maud::{Markup, html};
+
+use crate::{DestinationKind, Order, OrderStore, stations};
+
+impl Order {
+  /// Whether the order still has cars to pick up, or drop off, at `station`.
+  pub fn work_at(
+    &self,
+    side: DestinationKind,
+    station: Intern<String>,
+  ) -> bool {
+    self.status().is_active()
+      && self
+        .legs(side)
+        .any(|leg| leg.station == station && !leg.done)
+  }
+}
+
+impl OrderStore {
+  /// `orders` under a header for each station they still have work at, split
+  /// into what to pick up and what to drop off there. An order shows up at
+  /// every station it has work at, and orders with nothing left to do come
+  /// last.
+  pub fn render_by_station(&self, orders: &[&Order], now: u64) -> Markup {
+    let render_section = |title: &str, orders: &[&&Order]| {
+      html!(
+        @if !orders.is_empty() {
+          tr.station-section {
+            td colspan="10" { (title) }
+          }
+          @for order in orders {
+            (order.render(&self.warnings(order), now, &self.trains))
+          }
+        }
+      )
+    };
+    let at = |side, station| {
+      orders
+        .iter()
+        .filter(|o| o.work_at(side, station))
+        .collect::<Vec<_>>()
+    };
+    let idle: Vec<_> =
+      orders.iter().filter(|o| !o.status().is_active()).collect();
+
+    html!(
+      @for station in stations() {
+        @let pickups = at(DestinationKind::From, station.short);
+        @let dropoffs = at(DestinationKind::To, station.short);
+        @if !pickups.is_empty() || !dropoffs.is_empty() {
+          tr.station-header {
+            td.dest colspan="10" {
+              div.bg.(station.short) {}
+              div.content { (station.long) " (" (station.short) ")" }
+            }
+          }
+          (render_section("Pick up here", &pickups))
+          (render_section("Drop off here", &dropoffs))
+        }
+      }
+      @if !idle.is_empty() {
+        tr.station-header {
+          td colspan="10" { "Nothing left to do" }
+        }
+        (render_section("Delivered, completed or abandoned", &idle))
+      }
+    )
+  }
+}
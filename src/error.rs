@@ -0,0 +1,135 @@
+use std::io;
+
+use axum::{
+  http::StatusCode,
+  response::{Html, IntoResponse, Response},
+};
+use maud::html;
+
+use dv_tracker::{InvalidOrder, ParseError, PlanError, TransitionError};
+
+/// Everything that can go wrong while handling a request for a board.
+///
+/// Errors are rendered into the page's `#error` banner (or next to the parse
+/// input, for order strings) rather than wherever the request was aimed, so a
+/// failed request never wipes out the orders.
+#[derive(Debug)]
+pub enum AppError {
+  NoSuchBoard,
+  NoSuchOrder,
+  /// An order's extra track that isn't there (any more), or its first one,
+  /// which can't be removed.
+  NoSuchLeg,
+  NoSuchTrain,
+  NoSuchLoco,
+  /// An archived batch that has already been restored.
+  NoSuchBatch,
+  /// Only delivered, completed or abandoned orders can be archived.
+  NotFinished,
+  /// A change the order's status doesn't allow, like dropping cars off
+  /// before they were picked up.
+  Transition(TransitionError),
+  Parse {
+    input: String,
+    error: ParseError,
+  },
+  /// An edit would have sent an order somewhere that isn't on the map.
+  Invalid(InvalidOrder),
+  Plan(PlanError),
+  /// The request itself couldn't be read, like a number field that was left
+  /// blank. Holds what axum rejected it with.
+  Rejected(StatusCode, String),
+  /// A page template couldn't be read from `./public`.
+  Page(&'static str, io::Error),
+}
+
+impl AppError {
+  pub fn status(&self) -> StatusCode {
+    match self {
+      AppError::NoSuchBoard
+      | AppError::NoSuchOrder
+      | AppError::NoSuchLeg
+      | AppError::NoSuchTrain
+      | AppError::NoSuchLoco
+      | AppError::NoSuchBatch => StatusCode::NOT_FOUND,
+      AppError::NotFinished | AppError::Transition(_) => StatusCode::CONFLICT,
+      AppError::Parse { .. } | AppError::Invalid(_) | AppError::Plan(_) => {
+        StatusCode::UNPROCESSABLE_ENTITY
+      }
+      AppError::Rejected(status, _) => *status,
+      AppError::Page(..) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+  }
+}
+
+impl std::fmt::Display for AppError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      AppError::NoSuchBoard => write!(f, "No such board."),
+      AppError::NoSuchOrder => {
+        write!(f, "That order no longer exists. It may have been deleted.")
+      }
+      AppError::NoSuchLeg => {
+        write!(f, "That track is no longer on the order.")
+      }
+      AppError::NoSuchTrain => {
+        write!(f, "That train no longer exists. It may have been removed.")
+      }
+      AppError::NoSuchLoco => write!(f, "No such loco."),
+      AppError::NoSuchBatch => {
+        write!(f, "Those orders are no longer in the history.")
+      }
+      AppError::NotFinished => write!(
+        f,
+        "Only delivered, completed or abandoned orders can be archived."
+      ),
+      AppError::Transition(error) => write!(f, "Can't do that: {error}."),
+      AppError::Parse { error, .. } => write!(f, "{error}"),
+      AppError::Invalid(error) => write!(f, "Invalid order: {error}."),
+      AppError::Plan(error) => write!(f, "Can't plan a route: {error}."),
+      AppError::Rejected(_, error) => {
+        write!(f, "Couldn't read the request: {error}.")
+      }
+      AppError::Page(page, _) => write!(f, "Failed to read {page}."),
+    }
+  }
+}
+
+impl From<InvalidOrder> for AppError {
+  fn from(error: InvalidOrder) -> Self {
+    AppError::Invalid(error)
+  }
+}
+
+impl From<TransitionError> for AppError {
+  fn from(error: TransitionError) -> Self {
+    AppError::Transition(error)
+  }
+}
+
+impl IntoResponse for AppError {
+  fn into_response(self) -> Response {
+    if let AppError::Page(page, e) = &self {
+      tracing::error!("failed to read {page}: {e}");
+    }
+
+    let (target, body) = match &self {
+      AppError::Parse { input, error } => ("#parse-error", error.render(input)),
+      _ => (
+        "#error",
+        html!(
+          (self.to_string())
+          " "
+          button onclick="this.parentElement.remove()" { "Dismiss" }
+        ),
+      ),
+    };
+
+    (
+      self.status(),
+      [("HX-Retarget", target), ("HX-Reswap", "innerHTML")],
+      Html::from(html!(p.error { (body) }).into_string()),
+    )
+      .into_response()
+  }
+}
@@ -0,0 +1,75 @@
+//! Stand-ins for axum's `Form`, `Query` and `Path` that fail with an
+//! [`AppError`], so a bad request is shown in the page's `#error` banner
+//! instead of replacing whatever it was aimed at with axum's plain-text
+//! rejection.
+
+use axum::{
+  extract::{FromRequest, FromRequestParts, Request, rejection},
+  http::request::Parts,
+};
+use serde::de::DeserializeOwned;
+
+use crate::error::AppError;
+
+pub struct Form<T>(pub T);
+
+impl<T, S> FromRequest<S> for Form<T>
+where
+  T: DeserializeOwned,
+  S: Send + Sync,
+{
+  type Rejection = AppError;
+
+  async fn from_request(req: Request, state: &S) -> Result<Self, AppError> {
+    axum::Form::<T>::from_request(req, state)
+      .await
+      .map(|axum::Form(value)| Form(value))
+      .map_err(|e: rejection::FormRejection| {
+        AppError::Rejected(e.status(), e.body_text())
+      })
+  }
+}
+
+pub struct Query<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+  T: DeserializeOwned,
+  S: Send + Sync,
+{
+  type Rejection = AppError;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    state: &S,
+  ) -> Result<Self, AppError> {
+    axum::extract::Query::<T>::from_request_parts(parts, state)
+      .await
+      .map(|axum::extract::Query(value)| Query(value))
+      .map_err(|e: rejection::QueryRejection| {
+        AppError::Rejected(e.status(), e.body_text())
+      })
+  }
+}
+
+pub struct Path<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+  T: DeserializeOwned + Send,
+  S: Send + Sync,
+{
+  type Rejection = AppError;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    state: &S,
+  ) -> Result<Self, AppError> {
+    axum::extract::Path::<T>::from_request_parts(parts, state)
+      .await
+      .map(|axum::extract::Path(value)| Path(value))
+      .map_err(|e: rejection::PathRejection| {
+        AppError::Rejected(e.status(), e.body_text())
+      })
+  }
+}
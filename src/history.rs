@@ -0,0 +1,246 @@
+use maud::{Markup, html};
+use serde::{Deserialize, Serialize};
+
+use crate::{Order, OrderStore};
+
+/// A finished order that was taken off the board.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Archived {
+  pub order: Order,
+  /// When it was archived, in seconds since the Unix epoch.
+  pub archived_at: u64,
+  /// The play session it was archived in.
+  pub session: usize,
+  /// Orders archived together share a batch, and are restored together.
+  #[serde(default)]
+  pub batch: usize,
+}
+
+impl Archived {
+  /// When the order was finished, falling back to when it was archived for
+  /// orders finished before that was recorded.
+  pub fn finished_at(&self) -> u64 {
+    self.order.finished_at.unwrap_or(self.archived_at)
+  }
+
+  pub fn earnings(&self) -> u64 {
+    self.order.earnings(self.finished_at())
+  }
+}
+
+/// What a set of archived orders added up to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct SessionTotals {
+  pub jobs: usize,
+  pub tonnes: u64,
+  pub cars: u64,
+  pub earnings: u64,
+}
+
+impl SessionTotals {
+  fn add(&mut self, archived: &Archived) {
+    self.jobs += 1;
+    self.tonnes += u64::from(archived.order.tonnes);
+    self.cars += u64::from(archived.order.cars);
+    self.earnings += archived.earnings();
+  }
+}
+
+impl<'a> FromIterator<&'a Archived> for SessionTotals {
+  fn from_iter<I: IntoIterator<Item = &'a Archived>>(iter: I) -> Self {
+    let mut totals = Self::default();
+    for archived in iter {
+      totals.add(archived);
+    }
+    totals
+  }
+}
+
+/// The entries an event added to or took out of the history. Undo and redo
+/// replay these instead of keeping a copy of the whole history for every step.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistoryChange {
+  /// Newly archived entries, which always go at the end.
+  added: Vec<Archived>,
+  /// Restored entries, which are always a whole batch.
+  removed: Vec<Archived>,
+}
+
+impl HistoryChange {
+  pub fn is_empty(&self) -> bool {
+    self.added.is_empty() && self.removed.is_empty()
+  }
+
+  /// Takes the change back out of `history`.
+  pub(crate) fn undo(&self, history: &mut Vec<Archived>) {
+    history.truncate(history.len() - self.added.len());
+    history.extend(self.removed.iter().cloned());
+    // Batches only ever grow, so this puts a restored batch back in place.
+    history.sort_by_key(|a| a.batch);
+  }
+
+  /// Makes the change to `history` again.
+  pub(crate) fn redo(&self, history: &mut Vec<Archived>) {
+    if let Some(removed) = self.removed.first() {
+      history.retain(|a| a.batch != removed.batch);
+    }
+    history.extend(self.added.iter().cloned());
+  }
+}
+
+impl OrderStore {
+  /// Moves a delivered or closed order into the history. Active orders stay
+  /// put.
+  pub fn archive(&mut self, guid: usize, at: u64) -> HistoryChange {
+    self.archive_where(|o| o.guid == guid, at)
+  }
+
+  /// Moves every delivered or closed order into the history in one batch.
+  pub fn archive_finished(&mut self, at: u64) -> HistoryChange {
+    self.archive_where(|_| true, at)
+  }
+
+  fn archive_where(
+    &mut self,
+    matches: impl Fn(&Order) -> bool,
+    at: u64,
+  ) -> HistoryChange {
+    let batch = self.history.iter().map(|a| a.batch + 1).max().unwrap_or(0);
+    let (archived, kept): (Vec<_>, _) = std::mem::take(&mut self.orders)
+      .into_iter()
+      .partition(|o| !o.status().is_active() && matches(o));
+    self.orders = kept;
+    let added: Vec<_> = archived
+      .into_iter()
+      .map(|order| Archived {
+        order,
+        archived_at: at,
+        session: self.session,
+        batch,
+      })
+      .collect();
+    self.history.extend(added.iter().cloned());
+    HistoryChange {
+      added,
+      removed: Vec::new(),
+    }
+  }
+
+  /// Puts a batch of archived orders back at the end of the board.
+  pub fn restore(&mut self, batch: usize) -> HistoryChange {
+    let (removed, kept): (Vec<_>, _) = std::mem::take(&mut self.history)
+      .into_iter()
+      .partition(|a| a.batch == batch);
+    self.history = kept;
+    for Archived { order, .. } in &removed {
+      let mut order = order.clone();
+      self.clear_missing_train(&mut order);
+      self.orders.push(order);
+    }
+    HistoryChange {
+      added: Vec::new(),
+      removed,
+    }
+  }
+
+  /// How many orders were archived in a batch.
+  pub fn batch_len(&self, batch: usize) -> usize {
+    self.history.iter().filter(|a| a.batch == batch).count()
+  }
+
+  /// Starts a new session, unless nothing has been archived in the current
+  /// one yet.
+  pub fn new_session(&mut self) {
+    if self.history.iter().any(|a| a.session == self.session) {
+      self.session += 1;
+    }
+  }
+
+  /// Totals for every session with archived orders, most recent first.
+  pub fn session_totals(&self) -> Vec<(usize, SessionTotals)> {
+    (0..=self.session)
+      .rev()
+      .map(|session| {
+        let totals: SessionTotals = self
+          .history
+          .iter()
+          .filter(|a| a.session == session)
+          .collect();
+        (session, totals)
+      })
+      .filter(|(_, totals)| totals.jobs > 0)
+      .collect()
+  }
+
+  pub fn all_time_totals(&self) -> SessionTotals {
+    self.history.iter().collect()
+  }
+
+  /// The history page's contents: totals per session and overall, then every
+  /// archived order, most recent first.
+  pub fn render_history(&self) -> Markup {
+    let render_totals = |label: Markup, totals: &SessionTotals| {
+      html!(
+        tr {
+          td { (label) }
+          td { (totals.jobs) }
+          td { (totals.tonnes) "t" }
+          td { (totals.cars) }
+          td { "$" (totals.earnings) }
+        }
+      )
+    };
+
+    html!(
+      table {
+        thead {
+          tr {
+            th { "Session" }
+            th { "Jobs" }
+            th { "Tonnes" }
+            th { "Cars" }
+            th { "Earnings" }
+          }
+        }
+        tbody {
+          @for (session, totals) in self.session_totals() {
+            (render_totals(html!("Session " (session + 1)), &totals))
+          }
+          (render_totals(html!(b { "All time" }), &self.all_time_totals()))
+        }
+      }
+      h3 { "Orders" }
+      @if self.history.is_empty() {
+        p { "Nothing has been archived yet." }
+      } @else {
+        table {
+          thead {
+            tr {
+              th { "Session" }
+              th { "Finished" }
+              th { "Order" }
+              th { "Earned" }
+              th {}
+            }
+          }
+          tbody {
+            @for archived in self.history.iter().rev() {
+              tr {
+                td { (archived.session + 1) }
+                td { time data-unix=(archived.finished_at()) { (archived.finished_at()) } }
+                td { (archived.order) }
+                td { "$" (archived.earnings()) }
+                td {
+                  @let count = self.batch_len(archived.batch);
+                  button hx-post={"api/history/" (archived.batch) "/restore"} hx-target="#history" title={"Put the " (count) " order(s) archived together with this one back on the board"} {
+                    "Restore"
+                  }
+                }
+              }
+            }
+          }
+        }
+      }
+    )
+  }
+}
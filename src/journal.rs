@@ -0,0 +1,494 @@
+use std::{
+  fs::{self, File, OpenOptions},
+  io::{self, Write},
+  path::{Path, PathBuf},
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use internment::Intern;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  Closed, Destination, DestinationKind, InvalidOrder, Order, OrderKind, Train,
+};
+
+/// Which way to move an order in the list.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Direction {
+  Up,
+  Down,
+}
+
+/// An order string and the order read from it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Parsed {
+  pub input: String,
+  pub order: Order,
+}
+
+/// A partial update to an order. Fields that are `None` are left untouched.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OrderEdit {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub id: Option<u8>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub kind: Option<OrderKind>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub from_station: Option<Intern<String>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub from_yard: Option<Intern<String>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub from_track: Option<u8>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub to_station: Option<Intern<String>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub to_yard: Option<Intern<String>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub to_track: Option<u8>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub notes: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub tonnes: Option<u16>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub cars: Option<u16>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub payout: Option<u32>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub bonus: Option<u32>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub bonus_minutes: Option<u16>,
+}
+
+impl OrderEdit {
+  /// Applies the edit. Picking a new station without a yard moves to the
+  /// station's first yard and track, and likewise for a new yard without a
+  /// track, since the old ones won't exist there.
+  pub fn apply(&self, order: &mut Order) {
+    if let Some(id) = self.id {
+      order.id = id;
+    }
+    if let Some(kind) = self.kind {
+      order.kind = kind;
+    }
+
+    LegEdit {
+      station: self.from_station,
+      yard: self.from_yard,
+      track: self.from_track,
+      cars: None,
+    }
+    .apply(&mut order.from);
+    LegEdit {
+      station: self.to_station,
+      yard: self.to_yard,
+      track: self.to_track,
+      cars: None,
+    }
+    .apply(&mut order.to);
+
+    // Keep shunting orders at one station by bringing every other leg along.
+    if order.kind.stays_in_station() {
+      match (self.from_station, self.to_station) {
+        (Some(station), None) | (None, Some(station)) => {
+          order.move_to_station(station);
+        }
+        (Some(from), Some(to)) if from == to => order.move_to_station(from),
+        _ => {}
+      }
+    }
+
+    if let Some(notes) = &self.notes {
+      order.notes = notes.clone();
+    }
+    if let Some(tonnes) = self.tonnes {
+      order.tonnes = tonnes;
+    }
+    if let Some(cars) = self.cars {
+      order.cars = cars;
+    }
+    if let Some(payout) = self.payout {
+      order.payout = payout;
+    }
+    if let Some(bonus) = self.bonus {
+      order.bonus = bonus;
+    }
+    if let Some(bonus_minutes) = self.bonus_minutes {
+      order.bonus_minutes = bonus_minutes;
+    }
+  }
+
+  /// Checks that applying the edit to `order` would leave it valid, without
+  /// changing it.
+  pub fn check(&self, order: &Order) -> Result<(), InvalidOrder> {
+    let mut order = order.clone();
+    self.apply(&mut order);
+    order.validate()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.id.is_none()
+      && self.kind.is_none()
+      && self.from_station.is_none()
+      && self.from_yard.is_none()
+      && self.from_track.is_none()
+      && self.to_station.is_none()
+      && self.to_yard.is_none()
+      && self.to_track.is_none()
+      && self.notes.is_none()
+      && self.tonnes.is_none()
+      && self.cars.is_none()
+      && self.payout.is_none()
+      && self.bonus.is_none()
+      && self.bonus_minutes.is_none()
+  }
+}
+
+/// A partial update to one leg of an order, in the same way as [`OrderEdit`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LegEdit {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub station: Option<Intern<String>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub yard: Option<Intern<String>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub track: Option<u8>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub cars: Option<u16>,
+}
+
+impl LegEdit {
+  /// Applies the edit. A new station or yard without the rest resets to the
+  /// first yard or track, as with [`OrderEdit::apply`].
+  pub fn apply(&self, dest: &mut Destination) {
+    if let Some(station) = self.station
+      && station != dest.station
+    {
+      dest.station = station;
+      dest.reset_yard();
+    }
+    if let Some(yard) = self.yard
+      && yard != dest.yard
+    {
+      dest.yard = yard;
+      dest.reset_track();
+    }
+    if let Some(track) = self.track {
+      dest.track = track;
+    }
+    if let Some(cars) = self.cars {
+      dest.cars = cars;
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.station.is_none()
+      && self.yard.is_none()
+      && self.track.is_none()
+      && self.cars.is_none()
+  }
+}
+
+/// A partial update to a train, in the same way as [`OrderEdit`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TrainEdit {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub name: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub max_cars: Option<u16>,
+}
+
+impl TrainEdit {
+  pub fn apply(&self, train: &mut Train) {
+    if let Some(name) = &self.name {
+      train.name = name.clone();
+    }
+    if let Some(max_cars) = self.max_cars {
+      train.max_cars = max_cars;
+    }
+  }
+}
+
+/// A single mutation of an [`OrderStore`](crate::OrderStore). Replaying every
+/// event in order rebuilds the store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Event {
+  /// A blank order was added with the "Add" button.
+  Add {
+    order: Order,
+  },
+  /// An order was added from an order string.
+  Parse {
+    input: String,
+    order: Order,
+  },
+  /// Several orders were pasted in at once, and are undone together.
+  ParseAll {
+    orders: Vec<Parsed>,
+  },
+  Edit {
+    guid: usize,
+    edit: OrderEdit,
+  },
+  SetDone {
+    guid: usize,
+    destination: DestinationKind,
+    /// Which leg on that side; 0 is `from` or `to` itself.
+    #[serde(default)]
+    leg: usize,
+    done: bool,
+  },
+  /// Another track was added to one side of an order.
+  AddLeg {
+    guid: usize,
+    destination: DestinationKind,
+  },
+  EditLeg {
+    guid: usize,
+    destination: DestinationKind,
+    leg: usize,
+    edit: LegEdit,
+  },
+  RemoveLeg {
+    guid: usize,
+    destination: DestinationKind,
+    leg: usize,
+  },
+  /// The order's job was taken in game at `at`, starting its bonus timer, or
+  /// put back when `at` is `None`.
+  Take {
+    guid: usize,
+    at: Option<u64>,
+  },
+  Move {
+    guid: usize,
+    direction: Direction,
+  },
+  /// The order was put on a train, or taken off one with `None`.
+  AssignTrain {
+    guid: usize,
+    train: Option<usize>,
+  },
+  /// The order was completed or abandoned, or reopened with `None`.
+  Close {
+    guid: usize,
+    closed: Option<Closed>,
+  },
+  Duplicate {
+    guid: usize,
+  },
+  Remove {
+    guid: usize,
+  },
+  /// A finished order was taken off the board and into the history.
+  Archive {
+    guid: usize,
+  },
+  /// Every finished order was archived at once.
+  ArchiveFinished,
+  /// A batch of archived orders was put back on the board.
+  Restore {
+    batch: usize,
+  },
+  AddTrain {
+    name: String,
+  },
+  EditTrain {
+    train: usize,
+    edit: TrainEdit,
+  },
+  /// A train was removed, leaving its orders without one.
+  RemoveTrain {
+    train: usize,
+  },
+  /// A loco was put at the head of a train, or of the orders that aren't on
+  /// one when `train` is `None`. A `loco` of `None` takes it off again.
+  SetLoco {
+    train: Option<usize>,
+    loco: Option<Intern<String>>,
+  },
+  /// A new play session was started, so orders archived from now on are
+  /// totalled separately.
+  NewSession,
+  /// Reverts the most recent change that hasn't been undone yet.
+  Undo,
+  /// Reapplies the most recently undone change.
+  Redo,
+}
+
+impl Event {
+  /// Splits an edit into the events it represents: one for any field changes,
+  /// and one for each done checkbox that was set.
+  pub fn edits(
+    guid: usize,
+    edit: OrderEdit,
+    from_done: Option<bool>,
+    to_done: Option<bool>,
+  ) -> Vec<Self> {
+    let mut events = Vec::new();
+
+    if !edit.is_empty() {
+      events.push(Event::Edit { guid, edit });
+    }
+
+    for (destination, done) in [
+      (DestinationKind::From, from_done),
+      (DestinationKind::To, to_done),
+    ] {
+      if let Some(done) = done {
+        events.push(Event::SetDone {
+          guid,
+          destination,
+          leg: 0,
+          done,
+        });
+      }
+    }
+
+    events
+  }
+
+  /// Like [`Event::edits`], for a single leg of an order.
+  pub fn leg_edits(
+    guid: usize,
+    destination: DestinationKind,
+    leg: usize,
+    edit: LegEdit,
+    done: Option<bool>,
+  ) -> Vec<Self> {
+    let mut events = Vec::new();
+
+    if !edit.is_empty() {
+      events.push(Event::EditLeg {
+        guid,
+        destination,
+        leg,
+        edit,
+      });
+    }
+    if let Some(done) = done {
+      events.push(Event::SetDone {
+        guid,
+        destination,
+        leg,
+        done,
+      });
+    }
+
+    events
+  }
+}
+
+/// A journaled event along with who caused it and when.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+  pub seq: u64,
+  /// Seconds since the Unix epoch.
+  pub at: u64,
+  pub who: String,
+  pub event: Event,
+}
+
+/// The current time in seconds since the Unix epoch.
+pub fn unix_time() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or_default()
+}
+
+impl Entry {
+  pub fn new(seq: u64, who: impl Into<String>, event: Event) -> Self {
+    Self {
+      seq,
+      at: unix_time(),
+      who: who.into(),
+      event,
+    }
+  }
+}
+
+/// An append-only log of [`Entry`]s, stored as one JSON object per line.
+#[derive(Debug)]
+pub struct Journal {
+  file: File,
+  path: PathBuf,
+}
+
+impl Journal {
+  /// Opens (or creates) the journal at `path`, returning it along with every
+  /// entry already written to it.
+  ///
+  /// A bad last line is most likely the tail of a write that was cut short by
+  /// a crash, so the journal is truncated there with a warning instead of
+  /// failing. A bad line anywhere else is corruption, and an error.
+  pub fn open(path: impl AsRef<Path>) -> io::Result<(Self, Vec<Entry>)> {
+    let path = path.as_ref();
+    let contents = match fs::read_to_string(path) {
+      Ok(contents) => contents,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+      Err(e) => return Err(e),
+    };
+
+    let mut entries = Vec::new();
+    let mut valid_len = 0;
+    let lines = contents.split_inclusive('\n').collect::<Vec<_>>();
+    for (i, line) in lines.iter().enumerate() {
+      let is_last = i + 1 == lines.len();
+      match serde_json::from_str(line) {
+        Ok(entry) if line.ends_with('\n') => entries.push(entry),
+        Ok(_) => {
+          tracing::warn!("{path:?}:{}: ignoring unterminated entry", i + 1);
+          break;
+        }
+        Err(e) if is_last => {
+          tracing::warn!("{path:?}:{}: ignoring bad entry: {e}", i + 1);
+          break;
+        }
+        Err(e) => {
+          return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{path:?}:{}: bad entry: {e}", i + 1),
+          ));
+        }
+      }
+      valid_len += line.len();
+    }
+
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    if valid_len < contents.len() {
+      file.set_len(valid_len as u64)?;
+    }
+
+    let path = path.to_path_buf();
+    Ok((Self { file, path }, entries))
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Moves the journal file to `to` and keeps appending there.
+  pub fn rename(&mut self, to: impl AsRef<Path>) -> io::Result<()> {
+    let to = to.as_ref();
+    fs::rename(&self.path, to)?;
+    self.file = OpenOptions::new().append(true).open(to)?;
+    self.path = to.to_path_buf();
+    Ok(())
+  }
+
+  /// Appends an entry and flushes it to disk.
+  pub fn append(&mut self, entry: &Entry) -> io::Result<()> {
+    let mut line = serde_json::to_vec(entry).map_err(io::Error::other)?;
+    line.push(b'\n');
+    self.file.write_all(&line)?;
+    self.file.sync_data()
+  }
+}
@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Destination, ValidationError};
+
+/// The kinds of job the game hands out. Everything that differs between them
+/// lives here; new kinds only need a variant and an entry in each match.
+#[derive(
+  Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub enum OrderKind {
+  #[default]
+  #[serde(rename = "FH")]
+  FreightHaul,
+  #[serde(rename = "LH")]
+  LogisticHaul,
+  #[serde(rename = "SL")]
+  ShuntingLoad,
+  #[serde(rename = "SU")]
+  ShuntingUnload,
+}
+
+impl OrderKind {
+  pub const ALL: [OrderKind; 4] = [
+    OrderKind::FreightHaul,
+    OrderKind::LogisticHaul,
+    OrderKind::ShuntingLoad,
+    OrderKind::ShuntingUnload,
+  ];
+
+  /// The code used in order IDs, e.g. `FH`.
+  pub fn code(self) -> &'static str {
+    match self {
+      OrderKind::FreightHaul => "FH",
+      OrderKind::LogisticHaul => "LH",
+      OrderKind::ShuntingLoad => "SL",
+      OrderKind::ShuntingUnload => "SU",
+    }
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      OrderKind::FreightHaul => "Freight Haul",
+      OrderKind::LogisticHaul => "Logistic Haul",
+      OrderKind::ShuntingLoad => "Shunting Load",
+      OrderKind::ShuntingUnload => "Shunting Unload",
+    }
+  }
+
+  /// The background of the order's ID cell, matching the job booklets.
+  pub fn color(self) -> &'static str {
+    match self {
+      OrderKind::FreightHaul => "#5f915e",
+      OrderKind::LogisticHaul => "#dea94d",
+      OrderKind::ShuntingLoad | OrderKind::ShuntingUnload => "#b56862",
+    }
+  }
+
+  /// Shunting jobs move cars between tracks of a single station.
+  pub fn stays_in_station(self) -> bool {
+    matches!(self, OrderKind::ShuntingLoad | OrderKind::ShuntingUnload)
+  }
+
+  /// Checks that an order of this kind may go from `from` to `to`.
+  pub fn check_stations(
+    self,
+    from: &Destination,
+    to: &Destination,
+  ) -> Result<(), ValidationError> {
+    if self.stays_in_station() && from.station != to.station {
+      return Err(ValidationError::LeavesStation {
+        kind: self,
+        from: from.station,
+        to: to.station,
+      });
+    }
+
+    Ok(())
+  }
+}
+
+impl std::fmt::Display for OrderKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.code())
+  }
+}
+
+impl std::str::FromStr for OrderKind {
+  type Err = ();
+
+  /// From a code, e.g. `FH`.
+  fn from_str(code: &str) -> Result<Self, Self::Err> {
+    OrderKind::ALL
+      .into_iter()
+      .find(|kind| kind.code() == code)
+      .ok_or(())
+  }
+}
@@ -1,35 +1,53 @@
-use std::{collections::HashMap, sync::LazyLock};
+use std::{
+  collections::BTreeMap,
+  fs::{self, File},
+  io::{self, Write},
+  path::Path,
+};
 
 use internment::Intern;
 use itertools::Itertools;
 use maud::{Markup, html};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
-pub struct Station {
-  pub short: Intern<String>,
-  pub long: Intern<String>,
-  pub tracks: HashMap<Intern<String>, Vec<u8>>,
-}
+mod by_station;
+mod history;
+mod journal;
+mod kind;
+mod locos;
+mod parse;
+mod pay;
+mod plan;
+mod stations;
+mod status;
+mod tracks;
+mod trains;
+mod view;
 
-impl Station {
-  pub fn new(
-    short: impl AsRef<str>,
-    long: impl AsRef<str>,
-    tracks: HashMap<impl AsRef<str>, Vec<u8>>,
-  ) -> Self {
-    Self {
-      short: Intern::from_ref(short.as_ref()),
-      long: Intern::from_ref(long.as_ref()),
-      tracks: tracks
-        .into_iter()
-        .map(|(yard, tracks)| (Intern::from_ref(yard.as_ref()), tracks))
-        .collect(),
-    }
-  }
-}
+pub use history::{Archived, HistoryChange, SessionTotals};
+pub use journal::{
+  Direction, Entry, Event, Journal, LegEdit, OrderEdit, Parsed, TrainEdit,
+  unix_time,
+};
+pub use kind::OrderKind;
+pub use locos::{
+  Loco, LocosError, load_locos, loco, locos, parse_locos, set_locos,
+};
+pub use parse::{ParseError, Token, Tokens};
+pub use pay::format_time_left;
+pub use plan::{Action, Plan, PlanError, Stop, plan};
+pub use stations::{
+  Connection, Map, Station, StationsError, load_map, map, parse_map, set_map,
+  stations,
+};
+pub use status::{Closed, OrderStatus, TransitionError};
+pub use tracks::TrackUse;
+pub use trains::Train;
+pub use view::{GroupBy, OrderView, SortKey, StatusFilter};
 
-#[derive(Debug, Clone, Copy, PartialEq, Hash)]
-enum DestinationKind {
+#[derive(Debug, Clone, Copy, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DestinationKind {
   From,
   To,
 }
@@ -43,382 +61,1173 @@ impl std::fmt::Display for DestinationKind {
   }
 }
 
-fn render_kind_list(guid: usize, kind: Intern<String>) -> Markup {
+fn render_kind_list(guid: usize, kind: OrderKind) -> Markup {
   html!(
-    select name="kind" hx-post={"/api/order/" (guid)} hx-target="#orders" {
-      @for k in ["FH", "LH", "SL", "SU"] {
-        option value=(k) selected[Intern::from_ref(k) == kind] { (k) }
+    select name="kind" hx-post={"api/order/" (guid)} hx-target="#orders" title=(kind.name()) {
+      @for k in OrderKind::ALL {
+        option value=(k) selected[k == kind] title=(k.name()) { (k) }
       }
     }
   )
 }
 
-fn render_id_input(guid: usize, id: u8) -> Markup {
+fn render_train_list(
+  guid: usize,
+  train: Option<usize>,
+  trains: &[Train],
+) -> Markup {
   html!(
-    input name="id" type="number" hx-post={"/api/order/" (guid)} hx-target="#orders" value=(id) max="255" min="0";
+    select name="train" hx-post={"api/order/" (guid) "/train"} hx-target="#orders" title="Train" {
+      option value="" selected[train.is_none()] { "-" }
+      @for t in trains {
+        option value=(t.id) selected[train == Some(t.id)] { (t.name) }
+      }
+    }
   )
 }
 
-fn render_station_list(
-  guid: usize,
-  destination_kind: DestinationKind,
-  from: &Destination,
+/// The colours of order kinds and stations, which come from code and the map
+/// rather than the stylesheet.
+pub fn render_css() -> String {
+  let kinds = OrderKind::ALL.into_iter().map(|kind| {
+    format!(
+      "tr td.id.{kind} {{ background-color: {}; }}\n",
+      kind.color()
+    )
+  });
+  let stations = stations().iter().map(|station| {
+    format!(
+      "tr td.dest .bg.{} {{ background-color: {}; }}\n",
+      station.short, station.color
+    )
+  });
+
+  kinds.chain(stations).collect()
+}
+
+/// The form for pasting many order strings at once. Lines that failed to
+/// parse are put back in the textarea so they can be fixed, and listed below it
+/// with their errors.
+pub fn render_bulk_form(
+  added: usize,
+  failed: &[(usize, &str, ParseError)],
 ) -> Markup {
   html!(
-    select name={(destination_kind.to_string()) "-station"} hx-post={"/api/order/" (guid)} hx-target="#orders" {
-      @for s in STATIONS.iter() {
-        option value=(s.short) selected[*s.short == *from.station] { (s.short) }
+    form #bulk hx-put="api/parse-orders" hx-swap="outerHTML" {
+      textarea name="orders" rows="6" cols="40" placeholder="One order per line, e.g.\nFH01 SM B1 SW B1\nLH02 SW C3 SM A4 900 9" {
+        (failed.iter().map(|(_, line, _)| *line).join("\n"))
+      }
+      br;
+      button type="submit" { "Parse all" }
+      @if added > 0 {
+        p { "Added " (added) " order" @if added != 1 { "s" } "." }
+      }
+      @if !failed.is_empty() {
+        ul.error {
+          @for (line, input, error) in failed {
+            li { "Line " (line) ": " (error.render(input)) }
+          }
+        }
       }
     }
   )
 }
 
-fn render_yard_list(
-  guid: usize,
-  destination_kind: DestinationKind,
-  dest: &Destination,
-) -> Markup {
+fn render_leg_cars(url: &str, dest: &Destination) -> Markup {
+  html!(
+    input name="cars" type="number" hx-post=(url) hx-target="#orders" value=(dest.cars) min="0" title="Cars on this track";
+  )
+}
+
+/// The cell for an extra leg of an order, whose controls all post to `url`.
+/// `locked` disables its done checkbox, for when the order's status doesn't
+/// allow toggling it.
+fn render_leg(url: &str, dest: &Destination, locked: bool) -> Markup {
+  html!(
+    td .dest .completed[dest.done] {
+      div.bg.(dest.station) {}
+      div.content {
+        (render_station_list(url, "", dest))
+        (render_yard_list(url, "", dest))
+        (render_track_list(url, "", dest))
+        input name="done" type="checkbox" checked[dest.done] disabled[locked] hx-post=(url) hx-target="#orders" hx-vals="js:{'done':this.checked}";
+        (render_leg_cars(url, dest))
+        button hx-delete=(url) hx-target="#orders" title="Remove this track" { "x" }
+      }
+    }
+  )
+}
+
+fn render_id_input(guid: usize, id: u8) -> Markup {
   html!(
-    select name={(destination_kind.to_string()) "-yard"} hx-post={"/api/order/" (guid)} hx-target="#orders" {
-      @for y in STATIONS.iter().find(|s| s.short == dest.station).map(|s| s.tracks.keys().sorted()).unwrap_or_default() {
+    input name="id" type="number" hx-post={"api/order/" (guid)} hx-target="#orders" value=(id) max="255" min="0";
+  )
+}
+
+fn render_station_list(url: &str, prefix: &str, dest: &Destination) -> Markup {
+  html!(
+    select name={(prefix) "station"} hx-post=(url) hx-target="#orders" {
+      @for s in stations().iter() {
+        option value=(s.short) selected[*s.short == *dest.station] { (s.short) }
+      }
+    }
+  )
+}
+
+fn render_yard_list(url: &str, prefix: &str, dest: &Destination) -> Markup {
+  html!(
+    select name={(prefix) "yard"} hx-post=(url) hx-target="#orders" {
+      @for y in stations().iter().find(|s| s.short == dest.station).map(|s| s.tracks.keys().sorted()).unwrap_or_default() {
         option value=(y) selected[*y == dest.yard] { (y) }
       }
     }
   )
 }
 
-fn render_track_list(
-  guid: usize,
-  destination_kind: DestinationKind,
-  dest: &Destination,
-) -> Markup {
+fn render_track_list(url: &str, prefix: &str, dest: &Destination) -> Markup {
   html!(
-    select name={(destination_kind.to_string()) "-track"} hx-post={"/api/order/" (guid)} hx-target="#orders" {
-      @for t in STATIONS.iter().find(|s| s.short == dest.station).and_then(|s| s.tracks.get(&dest.yard)).unwrap_or(&vec![]).iter() {
+    select name={(prefix) "track"} hx-post=(url) hx-target="#orders" {
+      @for t in stations().iter().find(|s| s.short == dest.station).and_then(|s| s.tracks.get(&dest.yard)).unwrap_or(&vec![]).iter() {
         option value=(t) selected[*t == dest.track] { (t) }
       }
     }
   )
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Destination {
   pub station: Intern<String>,
   pub yard: Intern<String>,
   pub track: u8,
   pub done: bool,
+  /// How many of the order's cars are picked up or dropped off here, for
+  /// orders with more than one track on a side. Zero when not given.
+  pub cars: u16,
 }
 
+/// The first track of the first yard of the first station on the map.
 impl Default for Destination {
   fn default() -> Self {
+    let station = &stations()[0];
+    let (yard, tracks) = station
+      .tracks
+      .iter()
+      .min_by_key(|(yard, _)| *yard)
+      .expect("stations have at least one yard");
+
     Self {
-      station: Intern::from_ref("SM"),
-      yard: Intern::from_ref("A"),
-      track: 1,
+      station: station.short,
+      yard: *yard,
+      track: tracks[0],
       done: false,
+      cars: 0,
     }
   }
 }
 
 impl Destination {
-  /// Parse from station and yard/track strings. Example: `"SM"`, `"B1"`
-  pub fn parse(station: &str, yard_track: &str) -> Result<Self, String> {
-    let yard = yard_track.get(0..1).ok_or("missing yard")?;
-    let track = yard_track
-      .get(1..)
-      .ok_or("missing track")?
-      .parse::<u8>()
-      .map_err(|_| "invalid track number")?;
+  /// Parse from station and yard/track tokens. Example: `"SM"`, `"B1"`
+  pub fn parse(station: Token, yard_track: Token) -> Result<Self, ParseError> {
+    let (yard, track) = yard_track.split_at_digit();
+    yard.error_if_empty("missing yard, e.g. B1")?;
+    track.error_if_empty(&format!("missing track after yard {}", yard.text))?;
 
-    Ok(Self {
-      station: Intern::from_ref(station),
-      yard: Intern::from_ref(yard),
-      track,
+    let destination = Self {
+      station: Intern::from_ref(station.text),
+      yard: Intern::from_ref(yard.text),
+      track: track
+        .text
+        .parse::<u8>()
+        .map_err(|_| track.error("track must be a number from 0 to 255"))?,
       ..Default::default()
-    })
+    };
+
+    destination.validate().map_err(|e| match e {
+      ValidationError::UnknownStation { .. }
+      | ValidationError::LeavesStation { .. } => station.error(e.to_string()),
+      ValidationError::UnknownYard { .. } => yard.error(e.to_string()),
+      ValidationError::UnknownTrack { .. } => track.error(e.to_string()),
+    })?;
+
+    Ok(destination)
   }
 
-  pub fn make_valid(&mut self) {
-    if let Some(station) = STATIONS.iter().find(|s| s.short == self.station) {
-      if let Some(yard) = station.tracks.get(&self.yard) {
-        if !yard.contains(&self.track) {
-          self.track = *yard.first().unwrap();
-        }
-      } else {
-        self.yard = *station.tracks.keys().sorted().next().unwrap();
-        self.track = *station.tracks.get(&self.yard).unwrap().first().unwrap();
-      }
-    } else {
-      let first = STATIONS.first().unwrap();
-      self.station = first.short;
-      self.yard = *first.tracks.keys().sorted().next().unwrap();
-      self.track = *first.tracks.get(&self.yard).unwrap().first().unwrap();
+  /// Checks that the station, yard and track all exist.
+  pub fn validate(&self) -> Result<(), ValidationError> {
+    let Some(station) = stations().iter().find(|s| s.short == self.station)
+    else {
+      return Err(ValidationError::UnknownStation {
+        station: self.station,
+      });
+    };
+    let Some(tracks) = station.tracks.get(&self.yard) else {
+      return Err(ValidationError::UnknownYard {
+        station: station.short,
+        station_name: station.long,
+        yard: self.yard,
+        valid: station
+          .tracks
+          .iter()
+          .map(|(yard, tracks)| (*yard, tracks.clone()))
+          .collect(),
+      });
+    };
+    if !tracks.contains(&self.track) {
+      return Err(ValidationError::UnknownTrack {
+        station: station.short,
+        yard: self.yard,
+        track: self.track,
+        valid: tracks.clone(),
+      });
+    }
+
+    Ok(())
+  }
+
+  /// How many cars fit on the track, if that's known.
+  pub fn capacity(&self) -> Option<u16> {
+    stations()
+      .iter()
+      .find(|s| s.short == self.station)
+      .and_then(|s| s.capacity(self.yard, self.track))
+  }
+
+  /// Whether both point at the same track.
+  pub fn same_track(&self, other: &Destination) -> bool {
+    self.station == other.station
+      && self.yard == other.yard
+      && self.track == other.track
+  }
+
+  /// Moves to the first yard of the current station, and its first track.
+  pub fn reset_yard(&mut self) {
+    if let Some(station) = stations().iter().find(|s| s.short == self.station)
+      && let Some(yard) = station.tracks.keys().sorted().next()
+    {
+      self.yard = *yard;
+      self.reset_track();
     }
   }
+
+  /// Moves to the first track of the current yard.
+  pub fn reset_track(&mut self) {
+    if let Some(track) = stations()
+      .iter()
+      .find(|s| s.short == self.station)
+      .and_then(|s| s.tracks.get(&self.yard))
+      .and_then(|tracks| tracks.first())
+    {
+      self.track = *track;
+    }
+  }
+}
+
+/// In order string form, e.g. `SM B1`.
+impl std::fmt::Display for Destination {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} {}{}", self.station, self.yard, self.track)
+  }
+}
+
+/// Why a destination isn't on the map. Each error lists what would have been
+/// valid instead.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "error", rename_all = "kebab-case")]
+pub enum ValidationError {
+  UnknownStation {
+    station: Intern<String>,
+  },
+  UnknownYard {
+    station: Intern<String>,
+    station_name: Intern<String>,
+    yard: Intern<String>,
+    /// The station's yards and their tracks.
+    valid: BTreeMap<Intern<String>, Vec<u8>>,
+  },
+  UnknownTrack {
+    station: Intern<String>,
+    yard: Intern<String>,
+    track: u8,
+    valid: Vec<u8>,
+  },
+  /// A kind of order that has to stay within one station doesn't.
+  LeavesStation {
+    kind: OrderKind,
+    from: Intern<String>,
+    to: Intern<String>,
+  },
+}
+
+impl std::fmt::Display for ValidationError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ValidationError::UnknownStation { station } => write!(
+        f,
+        "unknown station `{station}`, expected one of {}",
+        stations().iter().map(|s| s.short).join(", ")
+      ),
+      ValidationError::UnknownYard {
+        station,
+        station_name,
+        yard,
+        valid,
+      } => write!(
+        f,
+        "yard {yard} is not in {station_name} ({station}), which has yards {}",
+        valid
+          .iter()
+          .map(|(yard, tracks)| format!(
+            "{yard} ({})",
+            tracks.iter().join(", ")
+          ))
+          .join(", ")
+      ),
+      ValidationError::UnknownTrack {
+        station,
+        yard,
+        track,
+        valid,
+      } => write!(
+        f,
+        "track {track} is not in {station} {yard}, which has tracks {}",
+        valid.iter().join(", ")
+      ),
+      ValidationError::LeavesStation { kind, from, to } => write!(
+        f,
+        "{} orders stay within one station, but this one goes from {from} to \
+         {to}",
+        kind.name()
+      ),
+    }
+  }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// A [`ValidationError`] in one of an order's destinations.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InvalidOrder {
+  pub destination: DestinationKind,
+  /// Which leg on that side; 0 is `from` or `to` itself.
+  pub leg: usize,
+  #[serde(flatten)]
+  pub error: ValidationError,
 }
 
-#[derive(Debug, Clone)]
+impl std::fmt::Display for InvalidOrder {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.leg {
+      0 => write!(f, "{}: {}", self.destination, self.error),
+      leg => write!(f, "{} leg {}: {}", self.destination, leg + 1, self.error),
+    }
+  }
+}
+
+impl std::error::Error for InvalidOrder {}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Order {
   pub guid: usize,
   pub id: u8,
-  pub kind: Intern<String>,
+  pub kind: OrderKind,
   pub from: Destination,
   pub to: Destination,
   pub notes: String,
   pub tonnes: u16,
   pub cars: u16,
+  /// More tracks to pick up from, after `from`.
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub more_from: Vec<Destination>,
+  /// More tracks to drop off at, after `to`.
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub more_to: Vec<Destination>,
+  /// Base pay for the job, in dollars.
+  pub payout: u32,
+  /// Extra pay for finishing within `bonus_minutes` of taking the job.
+  pub bonus: u32,
+  pub bonus_minutes: u16,
+  /// When the job was taken in game, in seconds since the Unix epoch.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub taken_at: Option<u64>,
+  /// When the last pickup or dropoff was checked off, in seconds since the
+  /// Unix epoch. Unset again if one is unchecked.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub finished_at: Option<u64>,
+  /// The id of the train the order is assigned to.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub train: Option<usize>,
+  /// Set once the order is completed or abandoned.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub closed: Option<Closed>,
 }
 
-impl Default for Order {
-  fn default() -> Self {
-    Self {
-      guid: 0,
-      id: Default::default(),
-      kind: Intern::from_ref("FH"),
-      from: Default::default(),
-      to: Default::default(),
-      notes: Default::default(),
-      tonnes: Default::default(),
-      cars: Default::default(),
+/// The canonical order string, e.g. `FH01 SM B1 SW B1 600 6 "fragile"`.
+/// Tonnes and cars are left off when they are zero, and so are the notes when
+/// there aren't any. Extra legs follow their side after a `+`, with their cars
+/// after a `:`, as in `SL02 FM A1:3 + FM A2:2 FM B5 300 5`. [`Order::parse`]
+/// reads it back.
+impl std::fmt::Display for Order {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}{:02}", self.kind, self.id)?;
+    for side in [DestinationKind::From, DestinationKind::To] {
+      let legs = self.legs(side).map(|leg| match leg.cars {
+        0 => leg.to_string(),
+        cars => format!("{leg}:{cars}"),
+      });
+      write!(f, " {}", legs.format(" + "))?;
+    }
+    if self.cars != 0 {
+      write!(f, " {} {}", self.tonnes, self.cars)?;
+    } else if self.tonnes != 0 {
+      write!(f, " {}", self.tonnes)?;
+    }
+    if !self.notes.is_empty() {
+      write!(f, " {}", parse::quote_notes(&self.notes))?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Reads one side of an order string: legs like `SM B1` or `SM B1:3`, joined
+/// by `+`. Each leg is checked against `kind`'s rules along with `first`, or
+/// the first leg read if there isn't one.
+fn parse_legs(
+  tokens: &mut Tokens,
+  side: &str,
+  kind: OrderKind,
+  first: Option<&Destination>,
+) -> Result<Vec<Destination>, ParseError> {
+  let mut legs: Vec<Destination> = Vec::new();
+  loop {
+    let station = tokens.next(&format!("{side} station"))?;
+    let (yard_track, cars) = tokens
+      .next(&format!("{side} yard and track"))?
+      .split_once(':');
+    let mut leg = Destination::parse(station.clone(), yard_track)?;
+    if let Some(cars) = cars {
+      leg.cars = cars.text.parse::<u16>().map_err(|_| {
+        cars.error("cars on a track must be a whole number up to 65535")
+      })?;
+    }
+    if let Some(first) = first.or(legs.first())
+      && let Err(e) = kind.check_stations(first, &leg)
+    {
+      return Err(station.error(e.to_string()));
+    }
+    legs.push(leg);
+
+    if !tokens.next_if("+") {
+      return Ok(legs);
     }
   }
 }
 
 impl Order {
-  /// From an order string. Example: `FH01 SM B1 SW A1`
-  pub fn parse(str: String) -> Result<Self, String> {
-    let str = str.to_uppercase();
-    let parts: Vec<_> = str.split(' ').filter(|s| s.is_empty()).collect();
-
-    let kind = str.get(0..2).ok_or("missing order kind")?;
-    let id = str
-      .get(2..4)
-      .ok_or("missing order id")?
+  /// From an order string. Example: `FH01 SM B1 SW B1 600 6 "fragile"`
+  pub fn parse(input: &str) -> Result<Self, ParseError> {
+    let (head, notes) = parse::split_notes(input)?;
+    let head = head.to_ascii_uppercase();
+    let mut tokens = Tokens::new(&head);
+
+    let (kind, id) = tokens
+      .next("order kind and id, e.g. FH01")?
+      .split_at_digit();
+    kind.error_if_empty("missing order kind, e.g. FH")?;
+    id.error_if_empty(&format!("missing order id after {}", kind.text))?;
+    let kind = kind.text.parse::<OrderKind>().map_err(|_| {
+      kind.error(format!(
+        "unknown order kind `{}`, expected one of {}",
+        kind.text,
+        OrderKind::ALL.iter().join(", ")
+      ))
+    })?;
+    let id = id
+      .text
       .parse::<u8>()
-      .map_err(|_| "invalid order id")?;
-
-    let from_station = *parts.get(1).ok_or("from: missing station")?;
-    let from_yard_track = *parts.get(2).ok_or("from: missing yard/track")?;
-    let to_station = *parts.get(3).ok_or("to: missing station")?;
-    let to_yard_track = *parts.get(4).ok_or("to: missing yard/track")?;
-
-    let tonnes = parts
-      .get(5)
-      .unwrap_or(&"0")
-      .parse::<u16>()
-      .map_err(|_| "invalid tonnes")?;
-    let cars = parts
-      .get(6)
-      .unwrap_or(&"0")
-      .parse::<u16>()
-      .map_err(|_| "invalid cars")?;
+      .map_err(|_| id.error("order id must be a number from 0 to 255"))?;
+
+    let mut from = parse_legs(&mut tokens, "from", kind, None)?;
+    let mut to = parse_legs(&mut tokens, "to", kind, from.first())?;
+    let more_from = from.split_off(1);
+    let more_to = to.split_off(1);
+
+    let mut number = |what: &str| match tokens.next_optional() {
+      Some(token) => token.text.parse::<u16>().map_err(|_| {
+        token.error(format!("{what} must be a whole number up to 65535"))
+      }),
+      None => Ok(0),
+    };
+    let tonnes = number("tonnes")?;
+    let cars = number("cars")?;
+    tokens.finish()?;
 
     Ok(Self {
-      kind: Intern::from_ref(kind),
+      kind,
       id,
-      from: Destination::parse(from_station, from_yard_track)
-        .map_err(|e| format!("from: {e}"))?,
-      to: Destination::parse(to_station, to_yard_track)
-        .map_err(|e| format!("to: {e}"))?,
+      from: from.remove(0),
+      to: to.remove(0),
+      more_from,
+      more_to,
+      notes,
       tonnes,
       cars,
       ..Default::default()
     })
   }
 
-  pub fn render(&self) -> Markup {
+  /// Parses one order string per line, skipping blank lines. Each result
+  /// comes with its line number, starting from one, and the line itself.
+  pub fn parse_lines(
+    input: &str,
+  ) -> Vec<(usize, &str, Result<Self, ParseError>)> {
+    input
+      .lines()
+      .enumerate()
+      .filter(|(_, line)| !line.trim().is_empty())
+      .map(|(i, line)| (i + 1, line, Self::parse(line)))
+      .collect()
+  }
+
+  /// Renders the order's row, with `warnings` shown under its destination,
+  /// followed by a row for each extra leg. `now` starts the bonus countdown,
+  /// which the page keeps ticking, and `trains` are what it can be assigned
+  /// to.
+  pub fn render(
+    &self,
+    warnings: &[String],
+    now: u64,
+    trains: &[Train],
+  ) -> Markup {
+    let url = format!("api/order/{}", self.guid);
+    let has_more = !self.more_from.is_empty() || !self.more_to.is_empty();
+    let status = self.status();
+
     html!(
       tr {
         td class={"id " (self.kind)} {
           (render_kind_list(self.guid, self.kind))
           (render_id_input(self.guid, self.id))
+          span class={"status " (status)} { (status.name()) }
         }
-        td .dest .completed[self.from.done]  {
-          div.bg.(self.from.station) {}
-          div.content {
-            (render_station_list(self.guid, DestinationKind::From, &self.from))
-            (render_yard_list(self.guid, DestinationKind::From, &self.from))
-            (render_track_list(self.guid, DestinationKind::From, &self.from))
-            input name="from-done" type="checkbox" checked[self.from.done] hx-post={"/api/order/" (self.guid)} hx-target="#orders" hx-vals="js:{'from-done':this.checked}";
+        @for side in [DestinationKind::From, DestinationKind::To] {
+          @let dest = self.legs(side).next().expect("there is always a first leg");
+          td .dest .completed[dest.done] {
+            div.bg.(dest.station) {}
+            div.content {
+              (render_station_list(&url, &format!("{side}-"), dest))
+              (render_yard_list(&url, &format!("{side}-"), dest))
+              (render_track_list(&url, &format!("{side}-"), dest))
+              input name={(side) "-done"} type="checkbox" checked[dest.done] disabled[!self.can_toggle(side, dest.done)] hx-post=(url) hx-target="#orders" hx-vals={"js:{'" (side) "-done':this.checked}"};
+              @if self.legs(side).nth(1).is_some() {
+                (render_leg_cars(&format!("{url}/legs/{side}/0"), dest))
+              }
+              button hx-post={(url) "/legs/" (side)} hx-target="#orders" title={"Add another " (side) " track"} { "+" }
+              @if side == DestinationKind::To {
+                @for warning in warnings {
+                  div.warning { (warning) }
+                }
+              }
+            }
           }
         }
-        td .dest .completed[self.to.done] {
-          div.bg.(self.to.station) {}
-          div.content {
-            (render_station_list(self.guid, DestinationKind::To, &self.to))
-            (render_yard_list(self.guid, DestinationKind::To, &self.to))
-            (render_track_list(self.guid, DestinationKind::To, &self.to))
-            input name="to-done" type="checkbox" checked[self.to.done] hx-post={"/api/order/" (self.guid)} hx-target="#orders" hx-vals="js:{'to-done':this.checked}";
-          }
+        td {
+          textarea name="notes" hx-post=(url) hx-target="#orders" { (self.notes.as_str()) }
         }
         td {
-          textarea name="notes" hx-post={"/api/order/" (self.guid)} hx-target="#orders" { (self.notes.as_str()) }
+          input name="tonnes" type="number" hx-post=(url) hx-target="#orders" value=(self.tonnes) min="0";
         }
         td {
-          input name="tonnes" type="number" hx-post={"/api/order/" (self.guid)} hx-target="#orders" value=(self.tonnes) min="0";
+          input name="cars" type="number" hx-post=(url) hx-target="#orders" value=(self.cars) min="0";
+        }
+        td.pay {
+          input name="payout" type="number" hx-post=(url) hx-target="#orders" value=(self.payout) min="0" title="Base pay";
+          " + "
+          input name="bonus" type="number" hx-post=(url) hx-target="#orders" value=(self.bonus) min="0" title="Bonus for finishing in time";
+        }
+        td.timer {
+          input name="bonus-minutes" type="number" hx-post=(url) hx-target="#orders" value=(self.bonus_minutes) min="0" title="Bonus time in minutes";
+          " min "
+          @if self.taken_at.is_none() {
+            button hx-post={(url) "/take"} hx-target="#orders" disabled[self.closed.is_some()] title="Start the bonus timer" { "Take" }
+          } @else {
+            @if let (Some(deadline), Some(left)) = (self.deadline(), self.time_left(now)) {
+              span.countdown.late[left < 0] data-deadline=(deadline) { (format_time_left(left)) }
+            } @else if !status.is_active() {
+              span { "done" }
+            }
+            button hx-delete={(url) "/take"} hx-target="#orders" disabled[self.closed.is_some()] title="Stop the bonus timer" { "x" }
+          }
         }
         td {
-          input name="cars" type="number" hx-post={"/api/order/" (self.guid)} hx-target="#orders" value=(self.cars) min="0";
+          (render_train_list(self.guid, self.train, trains))
         }
         td {
-          button hx-delete={"/api/order/" (self.guid)} hx-target="#orders" hx-trigger="click" hx-confirm="Sure?" {"x"}
-          button hx-post={"/api/order/" (self.guid) "/move/up"} hx-target="#orders" hx-trigger="click" {
+          button hx-delete=(url) hx-target="#orders" hx-trigger="click" hx-confirm="Sure?" {"x"}
+          button hx-post={(url) "/move/up"} hx-target="#orders" hx-trigger="click" {
             {"↑"}
           }
-          button hx-post={"/api/order/" (self.guid) "/move/down"} hx-target="#orders" hx-trigger="click" {
+          button hx-post={(url) "/move/down"} hx-target="#orders" hx-trigger="click" {
             {"↓"}
           }
-          button hx-post={"/api/order/" (self.guid) "/duplicate"} hx-target="#orders" hx-trigger="click" {
+          button hx-post={(url) "/duplicate"} hx-target="#orders" hx-trigger="click" {
             {"Dupe"}
           }
+          @if status == OrderStatus::Delivered {
+            button hx-post={(url) "/status"} hx-vals=r#"{"status": "completed"}"# hx-target="#orders" title="Turn the job in" {
+              {"Complete"}
+            }
+          }
+          @if status.is_active() || status == OrderStatus::Delivered {
+            button hx-post={(url) "/status"} hx-vals=r#"{"status": "abandoned"}"# hx-target="#orders" hx-confirm="Abandon this job? It won't pay anything." {
+              {"Abandon"}
+            }
+          } @else {
+            button hx-post={(url) "/status"} hx-vals=r#"{"status": "open"}"# hx-target="#orders" title="Undo completing or abandoning the job" {
+              {"Reopen"}
+            }
+          }
+          @if !status.is_active() {
+            button hx-post={(url) "/archive"} hx-target="#orders" title="Move to the history" {
+              {"Archive"}
+            }
+          }
+          button data-order=(self) onclick="copyText(this.dataset.order)" title={"Copy " (self)} {
+            {"Copy"}
+          }
+          @if has_more {
+            button onclick={"toggleLegs(" (self.guid) ")"} title="Show or hide the extra tracks" {
+              {"Legs"}
+            }
+          }
+        }
+      }
+      @for side in [DestinationKind::From, DestinationKind::To] {
+        @for (leg, dest) in self.legs(side).enumerate().skip(1) {
+          tr.leg data-guid=(self.guid) {
+            td.leg-name { "↳ " (side) " " (leg + 1) }
+            @if side == DestinationKind::To {
+              td {}
+            }
+            (render_leg(&format!("{url}/legs/{side}/{leg}"), dest, !self.can_toggle(side, dest.done)))
+            @if side == DestinationKind::From {
+              td {}
+            }
+            td colspan="7" {}
+          }
         }
       }
     )
   }
 
-  pub fn make_valid(&mut self) {
-    self.from.make_valid();
-    self.to.make_valid();
+  pub fn validate(&self) -> Result<(), InvalidOrder> {
+    for destination in [DestinationKind::From, DestinationKind::To] {
+      for (leg, dest) in self.legs(destination).enumerate() {
+        dest
+          .validate()
+          .and_then(|()| self.kind.check_stations(&self.from, dest))
+          .map_err(|error| InvalidOrder {
+            destination,
+            leg,
+            error,
+          })?;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Whether every pickup and dropoff has been checked off.
+  pub fn is_finished(&self) -> bool {
+    self
+      .legs(DestinationKind::From)
+      .chain(self.legs(DestinationKind::To))
+      .all(|leg| leg.done)
   }
+
+  /// The tracks on one side of the order, starting with `from` or `to`.
+  pub fn legs(
+    &self,
+    side: DestinationKind,
+  ) -> impl Iterator<Item = &Destination> {
+    let (first, more) = match side {
+      DestinationKind::From => (&self.from, &self.more_from),
+      DestinationKind::To => (&self.to, &self.more_to),
+    };
+    std::iter::once(first).chain(more)
+  }
+
+  /// Moves every leg that isn't at `station` there, to its first yard and
+  /// track, so that a shunting order stays at one station.
+  pub(crate) fn move_to_station(&mut self, station: Intern<String>) {
+    let more = self.more_from.iter_mut().chain(&mut self.more_to);
+    for leg in [&mut self.from, &mut self.to].into_iter().chain(more) {
+      if leg.station != station {
+        leg.station = station;
+        leg.reset_yard();
+      }
+    }
+  }
+
+  pub fn leg_mut(
+    &mut self,
+    side: DestinationKind,
+    leg: usize,
+  ) -> Option<&mut Destination> {
+    match (side, leg) {
+      (DestinationKind::From, 0) => Some(&mut self.from),
+      (DestinationKind::To, 0) => Some(&mut self.to),
+      (DestinationKind::From, leg) => self.more_from.get_mut(leg - 1),
+      (DestinationKind::To, leg) => self.more_to.get_mut(leg - 1),
+    }
+  }
+
+  /// How many of the order's cars are picked up or dropped off at a leg: its
+  /// own count if it has one, or all of them if it is the only leg on its side.
+  pub fn leg_cars(&self, side: DestinationKind, leg: &Destination) -> u16 {
+    if leg.cars > 0 {
+      leg.cars
+    } else if self.legs(side).count() == 1 {
+      self.cars
+    } else {
+      0
+    }
+  }
+
+  /// Applies an event that changes just this order. Others are ignored.
+  pub fn apply(&mut self, event: &Event) {
+    match event {
+      Event::Edit { edit, .. } => edit.apply(self),
+      Event::SetDone {
+        destination,
+        leg,
+        done,
+        ..
+      } => {
+        if let Some(leg) = self.leg_mut(*destination, *leg) {
+          leg.done = *done;
+        }
+      }
+      Event::AddLeg { destination, .. } => {
+        let mut leg = self
+          .legs(*destination)
+          .last()
+          .expect("there is always a first leg")
+          .clone();
+        leg.done = false;
+        leg.cars = 0;
+        match destination {
+          DestinationKind::From => self.more_from.push(leg),
+          DestinationKind::To => self.more_to.push(leg),
+        }
+      }
+      Event::EditLeg {
+        destination,
+        leg,
+        edit,
+        ..
+      } => {
+        if let Some(leg) = self.leg_mut(*destination, *leg) {
+          edit.apply(leg);
+        }
+        if self.kind.stays_in_station()
+          && let Some(station) = edit.station
+        {
+          self.move_to_station(station);
+        }
+      }
+      Event::RemoveLeg {
+        destination, leg, ..
+      } => {
+        let more = match destination {
+          DestinationKind::From => &mut self.more_from,
+          DestinationKind::To => &mut self.more_to,
+        };
+        // The first leg stays; it's `from` or `to` itself.
+        if (1..=more.len()).contains(leg) {
+          more.remove(leg - 1);
+        }
+      }
+      Event::Take { at, .. } => self.taken_at = *at,
+      Event::AssignTrain { train, .. } => self.train = *train,
+      Event::Close { closed, .. } => self.closed = *closed,
+      _ => {}
+    }
+  }
+
+  /// Checks that applying `event` would leave the order valid, without
+  /// changing it.
+  pub fn check(&self, event: &Event) -> Result<(), InvalidOrder> {
+    let mut order = self.clone();
+    order.apply(event);
+    order.validate()
+  }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct OrderStore {
+  /// The next guid to hand out. Persisted so that guids stay unique across
+  /// restarts.
+  idx: usize,
+  /// The sequence number of the last journal entry applied to this store.
+  #[serde(default)]
+  seq: u64,
+  pub orders: Vec<Order>,
+  /// Finished orders that were taken off the board, oldest first.
+  #[serde(default)]
+  pub history: Vec<Archived>,
+  /// The current play session, counting from 0. Archived orders are grouped
+  /// by it.
+  #[serde(default)]
+  pub session: usize,
+  #[serde(default)]
+  pub trains: Vec<Train>,
+  /// The loco pulling the orders that aren't on a train.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub loco: Option<Intern<String>>,
+  /// The next train id to hand out.
+  #[serde(default)]
+  train_idx: usize,
+  /// Earlier versions of the store, most recent last.
+  #[serde(skip)]
+  undo: Vec<Step>,
+  /// Versions of the store that were undone, most recent last.
+  #[serde(skip)]
+  redo: Vec<Step>,
+}
+
+/// The parts of an [`OrderStore`] that undo and redo bring back, apart from
+/// the history, which can grow long and only changes a batch at a time.
+#[derive(Debug, Clone, PartialEq)]
+struct Version {
+  orders: Vec<Order>,
+  session: usize,
+  trains: Vec<Train>,
+  loco: Option<Intern<String>>,
+  train_idx: usize,
+}
+
+/// An undoable step: the version from the other side of it, and how the
+/// event changed the history.
+#[derive(Debug)]
+struct Step {
+  version: Version,
+  history: HistoryChange,
 }
 
-pub static STATIONS: LazyLock<Vec<Station>> = LazyLock::new(|| {
-  vec![
-    Station::new(
-      "CME",
-      "Coal Mine East",
-      HashMap::from([
-        ("A", vec![3]),
-        ("B", vec![1, 2, 3, 5]),
-        ("C", vec![1, 3]),
-      ]),
-    ),
-    Station::new(
-      "CMS",
-      "Coal Mine South",
-      HashMap::from([("A", vec![1, 2]), ("B", vec![2, 3, 4, 5, 6, 7, 8])]),
-    ),
-    Station::new(
-      "CP",
-      "Coal Power Plant",
-      HashMap::from([("A", vec![1, 2, 3, 4, 5, 6]), ("B", vec![1, 2, 3, 5])]),
-    ),
-    Station::new(
-      "CS",
-      "City South",
-      HashMap::from([
-        ("A", vec![3]),
-        ("B", vec![1, 3, 4]),
-        ("C", vec![1, 3, 4]),
-      ]),
-    ),
-    Station::new(
-      "CW",
-      "City West",
-      HashMap::from([("A", vec![1, 3]), ("C", vec![2, 3, 4, 5, 6])]),
-    ),
-    Station::new(
-      "FF",
-      "Food Factory and Town",
-      HashMap::from([
-        ("A", vec![1]),
-        ("C", vec![1, 2, 3, 4, 5, 6, 7, 8, 9]),
-        ("D", vec![1, 2, 3, 4]),
-      ]),
-    ),
-    Station::new(
-      "FM",
-      "Farm",
-      HashMap::from([("A", vec![1, 2, 3]), ("B", vec![1, 2, 3, 5, 6])]),
-    ),
-    Station::new(
-      "FRC",
-      "Forest Central",
-      HashMap::from([("B", vec![1, 2, 4]), ("C", vec![1, 2, 4])]),
-    ),
-    Station::new(
-      "FRS",
-      "Forest South",
-      HashMap::from([("A", vec![1, 2, 3, 5, 6, 7])]),
-    ),
-    Station::new(
-      "GF",
-      "Goods Factory and Town",
-      HashMap::from([
-        ("A", vec![2, 3]),
-        ("B", vec![1, 2, 3]),
-        ("D", vec![1, 2, 3, 5, 6, 7]),
-      ]),
-    ),
-    Station::new(
-      "HB",
-      "Harbor and Town",
-      HashMap::from([
-        ("B", vec![1, 3, 4, 5, 6, 7, 8]),
-        ("C", vec![1, 2, 3]),
-        ("D", vec![1, 2, 3, 4, 5, 6, 7]),
-        ("E", vec![1, 2, 3, 4, 5, 7, 8, 9, 10, 11]),
-        ("G", vec![1, 2, 3, 5, 6, 7]),
-      ]),
-    ),
-    Station::new(
-      "IME",
-      "Iron Ore Mine East",
-      HashMap::from([
-        ("A", vec![1]),
-        ("B", vec![1, 2, 4]),
-        ("C", vec![1, 3, 4]),
-      ]),
-    ),
-    Station::new(
-      "IMW",
-      "Iron Ore Mine West",
-      HashMap::from([("A", vec![1, 2, 3, 4, 6, 7, 8])]),
-    ),
-    Station::new(
-      "MB",
-      "Military Base",
-      HashMap::from([("A", vec![1, 2]), ("B", vec![2, 3, 4, 5, 6])]),
-    ),
-    Station::new(
-      "MF",
-      "Machine Factory and Town",
-      HashMap::from([
-        ("B", vec![1, 2, 4, 5, 6]),
-        ("C", vec![1, 2, 3, 4]),
-        ("E", vec![1, 2, 3, 4]),
-      ]),
-    ),
-    Station::new(
-      "OR",
-      "Oil Refinery",
-      HashMap::from([
-        ("A", vec![1, 2, 3, 4, 5, 6]),
-        ("B", vec![3, 4, 5, 6, 7]),
-      ]),
-    ),
-    Station::new(
-      "OWC",
-      "Oil Well Central",
-      HashMap::from([("A", vec![1, 2, 3]), ("B", vec![1, 3, 4, 5, 6])]),
-    ),
-    Station::new(
-      "OWN",
-      "Oil Well North",
-      HashMap::from([("B", vec![2, 3, 4, 5, 6]), ("C", vec![1, 3])]),
-    ),
-    Station::new(
-      "SM",
-      "Steel Mill",
-      HashMap::from([
-        ("A", vec![3, 4, 5, 6, 7]),
-        ("B", vec![1, 2, 3, 4, 6, 7, 8]),
-      ]),
-    ),
-    Station::new(
-      "SW",
-      "Saw Mill",
-      HashMap::from([("B", vec![1, 3, 4]), ("C", vec![1, 3, 4])]),
-    ),
-  ]
-});
+/// How many versions of the orders are kept for undo.
+const HISTORY_LIMIT: usize = 100;
+
+impl OrderStore {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Loads a store from a JSON data file. A missing file yields an empty
+  /// store.
+  pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+    match fs::read_to_string(path) {
+      Ok(json) => serde_json::from_str(&json).map_err(io::Error::other),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::new()),
+      Err(e) => Err(e),
+    }
+  }
+
+  /// Saves the store to a JSON data file, as with [`Self::save_snapshot`].
+  pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+    Self::save_snapshot(path, &self.snapshot()?)
+  }
+
+  /// The store as it's saved to its data file.
+  pub fn snapshot(&self) -> io::Result<Vec<u8>> {
+    serde_json::to_vec(self).map_err(io::Error::other)
+  }
+
+  /// Writes a [`Self::snapshot`] to a JSON data file. The file is written and
+  /// synced next to the target first and then renamed over it, so a crash or
+  /// power loss mid-write never leaves a truncated file behind.
+  pub fn save_snapshot(
+    path: impl AsRef<Path>,
+    snapshot: &[u8],
+  ) -> io::Result<()> {
+    let path = path.as_ref();
+    let tmp = path.with_extension("tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(snapshot)?;
+    file.sync_all()?;
+    fs::rename(tmp, path)
+  }
+
+  pub fn seq(&self) -> u64 {
+    self.seq
+  }
+
+  /// Applies a journal entry. Entries at or before [`Self::seq`] have already
+  /// been applied and are skipped, which lets a snapshot be topped up by
+  /// replaying the whole journal over it.
+  pub fn apply(&mut self, entry: &Entry) {
+    if entry.seq <= self.seq {
+      return;
+    }
+    self.seq = entry.seq;
+
+    match &entry.event {
+      Event::Undo => {
+        if let Some(Step { version, history }) = self.undo.pop() {
+          let version = self.replace_version(version);
+          history.undo(&mut self.history);
+          self.redo.push(Step { version, history });
+        }
+      }
+      Event::Redo => {
+        if let Some(Step { version, history }) = self.redo.pop() {
+          let version = self.replace_version(version);
+          history.redo(&mut self.history);
+          self.undo.push(Step { version, history });
+        }
+      }
+      event => {
+        let before = self.version();
+        let history = self.apply_event(event, entry.at);
+        self.stamp_finished(entry.at);
+
+        // Only real changes are recorded, so undo never appears to do nothing.
+        if self.version() != before || !history.is_empty() {
+          if self.undo.len() == HISTORY_LIMIT {
+            self.undo.remove(0);
+          }
+          self.undo.push(Step {
+            version: before,
+            history,
+          });
+          self.redo.clear();
+        }
+      }
+    }
+  }
+
+  fn version(&self) -> Version {
+    Version {
+      orders: self.orders.clone(),
+      session: self.session,
+      trains: self.trains.clone(),
+      loco: self.loco,
+      train_idx: self.train_idx,
+    }
+  }
+
+  /// Swaps in `version`, returning the one it replaced.
+  fn replace_version(&mut self, version: Version) -> Version {
+    Version {
+      orders: std::mem::replace(&mut self.orders, version.orders),
+      session: std::mem::replace(&mut self.session, version.session),
+      trains: std::mem::replace(&mut self.trains, version.trains),
+      loco: std::mem::replace(&mut self.loco, version.loco),
+      train_idx: std::mem::replace(&mut self.train_idx, version.train_idx),
+    }
+  }
+
+  /// Records when orders were finished, and forgets it for any that were
+  /// unchecked again.
+  fn stamp_finished(&mut self, at: u64) {
+    for order in &mut self.orders {
+      match (order.is_finished(), order.finished_at) {
+        (true, None) => order.finished_at = Some(at),
+        (false, Some(_)) => order.finished_at = None,
+        _ => {}
+      }
+    }
+  }
+
+  pub fn can_undo(&self) -> bool {
+    !self.undo.is_empty()
+  }
+
+  pub fn can_redo(&self) -> bool {
+    !self.redo.is_empty()
+  }
+
+  /// Applies an event that happened at `at`, in seconds since the Unix epoch,
+  /// returning what it did to the history.
+  fn apply_event(&mut self, event: &Event, at: u64) -> HistoryChange {
+    match event {
+      Event::Undo | Event::Redo => {}
+      Event::Add { order } | Event::Parse { order, .. } => {
+        self.add(order.clone())
+      }
+      Event::ParseAll { orders } => {
+        for parsed in orders {
+          self.add(parsed.order.clone());
+        }
+      }
+      Event::Edit { guid, .. }
+      | Event::SetDone { guid, .. }
+      | Event::AddLeg { guid, .. }
+      | Event::EditLeg { guid, .. }
+      | Event::RemoveLeg { guid, .. }
+      | Event::Take { guid, .. }
+      | Event::AssignTrain { guid, .. }
+      | Event::Close { guid, .. } => {
+        if let Some(order) = self.get_mut(*guid) {
+          order.apply(event);
+        }
+      }
+      Event::Move { guid, direction } => self.move_order(*guid, *direction),
+      Event::Archive { guid } => return self.archive(*guid, at),
+      Event::ArchiveFinished => return self.archive_finished(at),
+      Event::Restore { batch } => return self.restore(*batch),
+      Event::NewSession => self.new_session(),
+      Event::AddTrain { name } => self.add_train(name),
+      Event::EditTrain { train, edit } => {
+        if let Some(train) = self.train_mut(*train) {
+          edit.apply(train);
+        }
+      }
+      Event::RemoveTrain { train } => self.remove_train(*train),
+      Event::SetLoco { train, loco } => self.set_loco(*train, *loco),
+      Event::Duplicate { guid } => self.duplicate(*guid),
+      Event::Remove { guid } => self.remove(*guid),
+    }
+    HistoryChange::default()
+  }
+
+  pub fn add(&mut self, mut order: Order) {
+    order.guid = self.idx;
+    self.clear_missing_train(&mut order);
+    self.orders.push(order);
+    self.idx += 1;
+  }
+
+  pub fn remove(&mut self, guid: usize) {
+    if let Some(pos) = self.orders.iter().position(|o| o.guid == guid) {
+      self.orders.remove(pos);
+    }
+  }
+
+  pub fn get(&self, guid: usize) -> Option<&Order> {
+    self.orders.iter().find(|o| o.guid == guid)
+  }
+
+  pub fn get_mut(&mut self, guid: usize) -> Option<&mut Order> {
+    self.orders.iter_mut().find(|o| o.guid == guid)
+  }
+
+  pub fn duplicate(&mut self, guid: usize) {
+    if let Some(order) = self.orders.iter().find(|o| o.guid == guid) {
+      let index = self.orders.iter().position(|o| o.guid == guid).unwrap();
+      let mut new_order = order.clone();
+      new_order.guid = self.idx;
+
+      self.orders.insert(index + 1, new_order);
+      self.idx += 1;
+    }
+  }
+
+  pub fn move_order(&mut self, guid: usize, direction: Direction) {
+    if let Some(pos) = self.orders.iter().position(|o| o.guid == guid) {
+      match direction {
+        Direction::Up if pos > 0 => {
+          let item = self.orders.remove(pos);
+          self.orders.insert(pos - 1, item);
+        }
+        Direction::Down if pos + 1 < self.orders.len() => {
+          let item = self.orders.remove(pos);
+          self.orders.insert(pos + 1, item);
+        }
+        _ => {}
+      }
+    }
+  }
+
+  /// Warnings about `order`'s cars not fitting on the track it's headed to,
+  /// either on their own or along with the other orders still headed there.
+  /// Tracks without a known capacity never warn.
+  pub fn warnings(&self, order: &Order) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if !order.status().is_active() {
+      return warnings;
+    }
+
+    for leg in order.legs(DestinationKind::To) {
+      let Some(capacity) = leg.capacity() else {
+        continue;
+      };
+      if leg.done {
+        continue;
+      }
+
+      let cars = order.leg_cars(DestinationKind::To, leg);
+      if cars > capacity {
+        warnings.push(format!(
+          "{cars} cars won't fit on {leg}, which holds {capacity}"
+        ));
+        continue;
+      }
+
+      let headed_there: u16 = self
+        .orders
+        .iter()
+        .filter(|o| o.status().is_active())
+        .flat_map(|o| {
+          o.legs(DestinationKind::To)
+            .filter(|to| !to.done && to.same_track(leg))
+            .map(|to| o.leg_cars(DestinationKind::To, to))
+        })
+        .fold(0, u16::saturating_add);
+      if headed_there > capacity {
+        warnings.push(format!(
+          "orders headed to {leg} add up to {headed_there} cars, but it holds \
+           {capacity}"
+        ));
+      }
+    }
+
+    warnings
+  }
+
+  pub fn total_mass(&self) -> u64 {
+    self.orders.iter().map(|o| u64::from(o.tonnes)).sum()
+  }
+
+  pub fn total_length(&self) -> u64 {
+    self.orders.iter().map(|o| u64::from(o.cars)).sum()
+  }
+
+  pub fn render(&self) -> Markup {
+    self.render_view(&OrderView::default())
+  }
+
+  /// Renders just the orders `view` shows, in its order and grouped the way it
+  /// asks. The totals are still for the whole board.
+  pub fn render_view(&self, view: &OrderView) -> Markup {
+    let now = unix_time();
+    let shown = view.apply(&self.orders);
+
+    html!(
+      @if view.group == Some(GroupBy::Station) {
+        (self.render_by_station(&shown, now))
+      } @else {
+        @for order in &shown {
+          (order.render(&self.warnings(order), now, &self.trains))
+        }
+      }
+      tr {
+        td { b { "Totals:" } }
+        td colspan="3" {
+          @if shown.len() < self.orders.len() {
+            (shown.len()) " shown of "
+          }
+          b { (self.orders.len()) }
+        }
+        td { b { (self.total_mass()) "t" } }
+        td { b { (self.total_length()) " cars" } }
+        td { b { "$" (self.expected_payout(now)) } }
+        td { b { "$" (self.bonus_at_risk(now)) } " at risk" }
+        td colspan="2" {}
+      }
+      (self.render_train_totals())
+    )
+  }
+}
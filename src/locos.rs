@@ -0,0 +1,113 @@
+use std::{collections::HashSet, fs, io, path::Path, sync::OnceLock};
+
+use internment::Intern;
+use serde::Deserialize;
+
+/// The locos that ship with the tracker.
+const BUILT_IN: &str = include_str!("../locos.toml");
+
+static LOCOS: OnceLock<Vec<Loco>> = OnceLock::new();
+
+/// Rolling and curve resistance, as a fraction of the train's weight.
+const RESISTANCE: f32 = 0.004;
+
+/// Standard gravity in m/s².
+const GRAVITY: f32 = 9.81;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Loco {
+  pub code: Intern<String>,
+  pub name: String,
+  /// In tonnes, including any tender.
+  pub mass: f32,
+  /// Starting tractive effort in kN.
+  pub tractive_kn: f32,
+}
+
+impl Loco {
+  /// Roughly how many tonnes the loco can start moving up a `grade` in
+  /// percent, not counting itself.
+  pub fn max_tonnes(&self, grade: f32) -> u32 {
+    let pulls = self.tractive_kn / (GRAVITY * (RESISTANCE + grade / 100.0));
+    (pulls - self.mass).max(0.0) as u32
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LocosFile {
+  loco: Vec<Loco>,
+}
+
+/// Why a locos file couldn't be loaded.
+#[derive(Debug)]
+pub enum LocosError {
+  Io(io::Error),
+  Toml(toml::de::Error),
+  /// The file parsed, but doesn't describe usable locos.
+  Invalid(String),
+}
+
+impl std::fmt::Display for LocosError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      LocosError::Io(e) => write!(f, "{e}"),
+      LocosError::Toml(e) => write!(f, "{e}"),
+      LocosError::Invalid(message) => write!(f, "{message}"),
+    }
+  }
+}
+
+impl std::error::Error for LocosError {}
+
+/// The locos: the ones passed to [`set_locos`], or the built-in ones if it was
+/// never called.
+pub fn locos() -> &'static [Loco] {
+  LOCOS.get_or_init(|| {
+    parse_locos(BUILT_IN).expect("built-in locos.toml is valid")
+  })
+}
+
+pub fn loco(code: Intern<String>) -> Option<&'static Loco> {
+  locos().iter().find(|l| l.code == code)
+}
+
+/// Replaces the built-in locos. Like [`set_map`](crate::set_map), this has to
+/// happen before anything has looked at them.
+pub fn set_locos(locos: Vec<Loco>) -> Result<(), LocosError> {
+  LOCOS.set(locos).map_err(|_| {
+    LocosError::Invalid("the locos have already been loaded".to_string())
+  })
+}
+
+/// Reads and validates a locos file.
+pub fn load_locos(path: impl AsRef<Path>) -> Result<Vec<Loco>, LocosError> {
+  let contents = fs::read_to_string(path).map_err(LocosError::Io)?;
+  parse_locos(&contents)
+}
+
+/// Parses and validates locos in the format of `locos.toml`.
+pub fn parse_locos(toml: &str) -> Result<Vec<Loco>, LocosError> {
+  let file = toml::from_str::<LocosFile>(toml).map_err(LocosError::Toml)?;
+  let invalid = |message: String| Err(LocosError::Invalid(message));
+
+  let mut seen = HashSet::new();
+  for loco in &file.loco {
+    let code = loco.code;
+    if code.is_empty() {
+      return invalid("a loco has an empty code".to_string());
+    }
+    if !seen.insert(code) {
+      return invalid(format!("loco `{code}` is listed twice"));
+    }
+    if !(loco.mass.is_finite() && loco.mass > 0.0) {
+      return invalid(format!("loco {code} must weigh more than 0 tonnes"));
+    }
+    if !(loco.tractive_kn.is_finite() && loco.tractive_kn > 0.0) {
+      return invalid(format!("loco {code} must pull with more than 0 kN"));
+    }
+  }
+
+  Ok(file.loco)
+}
@@ -1,21 +1,37 @@
-use core::net::SocketAddr;
-use std::sync::{Arc, Mutex};
+use core::{convert::Infallible, net::SocketAddr};
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
 
 use axum::{
-  Form, Router,
-  extract::{Path, State},
-  http::header,
-  response::Html,
+  Router,
+  extract::{ConnectInfo, FromRequestParts, State},
+  http::{Uri, header, request::Parts},
+  response::{
+    Html, IntoResponse,
+    sse::{self, KeepAlive, Sse},
+  },
   routing::{delete, get, post, put},
 };
 use clap::Parser;
 use internment::Intern;
-use maud::{Markup, html};
+use maud::html;
 use serde::Deserialize;
-use tokio::net::TcpListener;
+use tokio::{net::TcpListener, sync::Mutex};
+use tokio_stream::{StreamExt, wrappers::BroadcastStream};
 use tower_http::{cors::CorsLayer, services::ServeDir, trace::TraceLayer};
 
-use dv_tracker::Order;
+use dv_tracker::{
+  Closed, DestinationKind, Direction, Event, LegEdit, Order, OrderEdit,
+  OrderKind, OrderView, Parsed, TrainEdit, render_bulk_form, unix_time,
+};
+
+mod api;
+mod board;
+mod error;
+mod extract;
+
+use board::Board;
+use error::AppError;
+use extract::{Form, Path, Query};
 
 /// DV Tracker Server
 #[derive(Parser, Debug)]
@@ -24,6 +40,21 @@ struct Args {
   /// Host address to bind to
   #[arg(long, default_values = ["[::]:3000", "0.0.0.0:3000"])]
   address: Vec<SocketAddr>,
+
+  /// Directory boards are saved to and loaded from. Each board keeps its
+  /// orders in `<name>.json` and a log of every change in `<name>.jsonl`.
+  #[arg(long, default_value = "./data")]
+  data_dir: PathBuf,
+
+  /// A TOML file of stations to use instead of the built-in map, in the same
+  /// format as `stations.toml`
+  #[arg(long)]
+  stations: Option<PathBuf>,
+
+  /// A TOML file of locos to use instead of the built-in ones, in the same
+  /// format as `locos.toml`
+  #[arg(long)]
+  locos: Option<PathBuf>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -46,7 +77,7 @@ impl Bool {
 #[serde(rename_all = "kebab-case")]
 struct OrderRequest {
   id: Option<u8>,
-  kind: Option<Intern<String>>,
+  kind: Option<OrderKind>,
   from_station: Option<Intern<String>>,
   from_yard: Option<Intern<String>>,
   from_track: Option<u8>,
@@ -58,89 +89,391 @@ struct OrderRequest {
   notes: Option<String>,
   tonnes: Option<u16>,
   cars: Option<u16>,
+  payout: Option<u32>,
+  bonus: Option<u32>,
+  bonus_minutes: Option<u16>,
+}
+
+impl OrderRequest {
+  fn into_events(self, guid: usize) -> Vec<Event> {
+    let edit = OrderEdit {
+      id: self.id,
+      kind: self.kind,
+      from_station: self.from_station,
+      from_yard: self.from_yard,
+      from_track: self.from_track,
+      to_station: self.to_station,
+      to_yard: self.to_yard,
+      to_track: self.to_track,
+      notes: self.notes,
+      tonnes: self.tonnes,
+      cars: self.cars,
+      payout: self.payout,
+      bonus: self.bonus,
+      bonus_minutes: self.bonus_minutes,
+    };
+
+    Event::edits(
+      guid,
+      edit,
+      self.from_done.map(|done| done.checked()),
+      self.to_done.map(|done| done.checked()),
+    )
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct LegRequest {
+  station: Option<Intern<String>>,
+  yard: Option<Intern<String>>,
+  track: Option<u8>,
+  cars: Option<u16>,
+  done: Option<Bool>,
+}
+
+impl LegRequest {
+  fn into_events(
+    self,
+    guid: usize,
+    destination: DestinationKind,
+    leg: usize,
+  ) -> Vec<Event> {
+    let edit = LegEdit {
+      station: self.station,
+      yard: self.yard,
+      track: self.track,
+      cars: self.cars,
+    };
+
+    Event::leg_edits(
+      guid,
+      destination,
+      leg,
+      edit,
+      self.done.map(|done| done.checked()),
+    )
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct TrainRequest {
+  /// A train's id, or blank for none.
+  train: String,
+}
+
+impl TrainRequest {
+  fn train(&self) -> Result<Option<usize>, AppError> {
+    if self.train.is_empty() {
+      return Ok(None);
+    }
+    self
+      .train
+      .parse()
+      .map(Some)
+      .map_err(|_| AppError::NoSuchTrain)
+  }
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
-struct ManualOrderRequest {
-  order: String,
+enum StatusChoice {
+  Completed,
+  Abandoned,
+  /// Reopens a completed or abandoned order.
+  Open,
 }
 
-struct OrderStore {
-  idx: usize,
-  orders: Vec<Order>,
+#[derive(Debug, Deserialize)]
+struct StatusRequest {
+  status: StatusChoice,
 }
 
-impl OrderStore {
-  fn new() -> Self {
-    Self {
-      idx: 0,
-      orders: Vec::new(),
+impl StatusRequest {
+  fn closed(&self) -> Option<Closed> {
+    match self.status {
+      StatusChoice::Completed => Some(Closed::Completed),
+      StatusChoice::Abandoned => Some(Closed::Abandoned),
+      StatusChoice::Open => None,
     }
   }
+}
+
+#[derive(Debug, Deserialize)]
+struct LocoRequest {
+  /// A train's id, or blank for the orders that aren't on one.
+  train: String,
+  /// A loco's code, or blank for none.
+  loco: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct NewTrainRequest {
+  name: String,
+}
 
-  fn add(&mut self, mut order: Order) {
-    order.guid = self.idx;
-    self.orders.push(order);
-    self.idx += 1;
+#[derive(Debug, Deserialize)]
+pub struct PlanRequest {
+  /// Station to start from. Blank means the first stop on the board.
+  #[serde(default)]
+  start: String,
+}
+
+impl PlanRequest {
+  pub fn start(&self) -> Option<Intern<String>> {
+    let start = self.start.trim();
+    (!start.is_empty()).then(|| Intern::from(start.to_uppercase()))
   }
+}
+
+#[derive(Debug, Deserialize)]
+struct BulkOrderRequest {
+  /// One order string per line.
+  orders: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ManualOrderRequest {
+  order: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct BoardRequest {
+  name: String,
+}
+
+#[derive(Deserialize)]
+struct BoardPath {
+  board: String,
+}
 
-  fn remove(&mut self, guid: usize) {
-    if let Some(pos) = self.orders.iter().position(|o| o.guid == guid) {
-      self.orders.remove(pos);
+#[derive(Deserialize)]
+struct GuidPath {
+  guid: usize,
+}
+
+#[derive(Deserialize)]
+struct SidePath {
+  guid: usize,
+  destination: DestinationKind,
+}
+
+#[derive(Deserialize)]
+struct LegPath {
+  guid: usize,
+  destination: DestinationKind,
+  leg: usize,
+}
+
+#[derive(Deserialize)]
+struct TrainPath {
+  train: usize,
+}
+
+#[derive(Deserialize)]
+struct BatchPath {
+  batch: usize,
+}
+
+#[derive(Deserialize)]
+struct MovePath {
+  guid: usize,
+  direction: Direction,
+}
+
+const INVALID_NAME: &str = "Board names may only use letters, digits, - and _.";
+const NAME_TAKEN: &str = "That board already exists.";
+
+#[derive(Clone)]
+struct AppState {
+  boards: Arc<Mutex<BTreeMap<String, Arc<Board>>>>,
+  data_dir: Arc<PathBuf>,
+}
+
+impl AppState {
+  fn new(boards: BTreeMap<String, Arc<Board>>, data_dir: PathBuf) -> Self {
+    Self {
+      boards: Arc::new(Mutex::new(boards)),
+      data_dir: Arc::new(data_dir),
     }
   }
 
-  fn get_mut(&mut self, guid: usize) -> Option<&mut Order> {
-    self.orders.iter_mut().find(|o| o.guid == guid)
+  async fn render_boards(&self, error: Option<&str>) -> Html<String> {
+    let boards = self.boards.lock().await;
+    Html::from(board::render_list(boards.keys(), error).into_string())
   }
+}
 
-  fn duplicate(&mut self, guid: usize) {
-    if let Some(order) = self.orders.iter().find(|o| o.guid == guid) {
-      let index = self.orders.iter().position(|o| o.guid == guid).unwrap();
-      let mut new_order = order.clone();
-      new_order.guid = self.idx;
+/// The board named by the `{board}` segment of the path.
+struct CurrentBoard(Arc<Board>);
 
-      self.orders.insert(index + 1, new_order);
-      self.idx += 1;
-    }
+impl FromRequestParts<AppState> for CurrentBoard {
+  type Rejection = AppError;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    state: &AppState,
+  ) -> Result<Self, Self::Rejection> {
+    let Path(BoardPath { board }) =
+      Path::<BoardPath>::from_request_parts(parts, state).await?;
+
+    state
+      .boards
+      .lock()
+      .await
+      .get(&board)
+      .cloned()
+      .map(CurrentBoard)
+      .ok_or(AppError::NoSuchBoard)
   }
+}
+
+/// The view of the board a request came from. htmx sends the page's URL along
+/// in `HX-Current-URL`, so a change re-renders the orders filtered and sorted
+/// the way the player who made it is looking at them.
+struct CurrentView(OrderView);
+
+impl<S: Send + Sync> FromRequestParts<S> for CurrentView {
+  type Rejection = Infallible;
 
-  fn total_mass(&self) -> u16 {
-    self.orders.iter().map(|o| o.tonnes).sum()
+  async fn from_request_parts(
+    parts: &mut Parts,
+    _state: &S,
+  ) -> Result<Self, Self::Rejection> {
+    let view = parts
+      .headers
+      .get("HX-Current-URL")
+      .and_then(|url| url.to_str().ok()?.parse::<Uri>().ok())
+      .map(|uri| view_of(&uri))
+      .unwrap_or_default();
+
+    Ok(CurrentView(view))
   }
+}
 
-  fn total_length(&self) -> u16 {
-    self.orders.iter().map(|o| o.cars).sum()
+/// Reads the view from `uri`'s query string. One that can't be read, say from
+/// a bookmark made before a filter changed, shows the default view instead.
+fn view_of(uri: &Uri) -> OrderView {
+  axum::extract::Query::try_from_uri(uri)
+    .map(|axum::extract::Query(view)| view)
+    .unwrap_or_default()
+}
+
+/// Checks that `events` can be applied to `order` one after the other: each
+/// has to leave it valid and be a step its status allows at that point.
+fn check_events(order: &Order, events: &[Event]) -> Result<(), AppError> {
+  let mut order = order.clone();
+  for event in events {
+    order.check(event)?;
+    order.check_transition(event)?;
+    order.apply(event);
   }
+  Ok(())
+}
+
+async fn board_page(
+  CurrentBoard(board): CurrentBoard,
+  Path(BoardPath { board: name }): Path<BoardPath>,
+  uri: Uri,
+) -> Result<impl IntoResponse, AppError> {
+  let view = view_of(&uri);
+  let html = std::fs::read_to_string("./public/index.html")
+    .map_err(|e| AppError::Page("index.html", e))?;
+  let store = board.store.lock().await;
 
-  fn render(&self) -> Markup {
-    html!(
-      @for order in &self.orders {
-        (order.render())
+  Ok((
+    [(header::CACHE_CONTROL, "no-store")],
+    Html::from(fill(
+      &html,
+      &[
+        ("board", name),
+        ("filter", view.render_form().into_string()),
+        ("orders", store.render_view(&view).into_string()),
+        ("bulk", render_bulk_form(0, &[]).into_string()),
+      ],
+    )),
+  ))
+}
+
+/// Fills in the `{{key}}` placeholders of a page template in a single pass,
+/// so that text filled in from the board is never taken for a placeholder.
+fn fill(template: &str, values: &[(&str, String)]) -> String {
+  let mut page = String::with_capacity(template.len());
+  let mut rest = template;
+  while let Some(start) = rest.find("{{") {
+    let (before, after) = rest.split_at(start);
+    page.push_str(before);
+    let value = after[2..].split_once("}}").and_then(|(key, tail)| {
+      let (_, value) = values.iter().find(|(k, _)| *k == key)?;
+      Some((value, tail))
+    });
+    match value {
+      Some((value, tail)) => {
+        page.push_str(value);
+        rest = tail;
       }
-      tr {
-        td { b { "Totals:" } }
-        td colspan="3" { b { (self.orders.len()) } }
-        td { b { (self.total_mass()) "t" } }
-        td { b { (self.total_length()) " cars" } }
-        td {}
+      None => {
+        page.push_str("{{");
+        rest = &after[2..];
       }
-    )
+    }
   }
+  page.push_str(rest);
+  page
 }
 
-#[derive(Clone)]
-struct AppState {
-  store: Arc<Mutex<OrderStore>>,
+async fn tracks_page(
+  CurrentBoard(board): CurrentBoard,
+  Path(BoardPath { board: name }): Path<BoardPath>,
+) -> Result<impl IntoResponse, AppError> {
+  let html = std::fs::read_to_string("./public/tracks.html")
+    .map_err(|e| AppError::Page("tracks.html", e))?;
+  let store = board.store.lock().await;
+
+  Ok((
+    [(header::CACHE_CONTROL, "no-store")],
+    Html::from(html.replace("{{board}}", &name).replace(
+      "{{tracks}}",
+      store.render_occupancy().into_string().as_str(),
+    )),
+  ))
 }
 
-impl AppState {
-  fn new() -> Self {
-    Self {
-      store: Arc::new(Mutex::new(OrderStore::new())),
-    }
-  }
+async fn history_page(
+  CurrentBoard(board): CurrentBoard,
+  Path(BoardPath { board: name }): Path<BoardPath>,
+) -> Result<impl IntoResponse, AppError> {
+  let html = std::fs::read_to_string("./public/history.html")
+    .map_err(|e| AppError::Page("history.html", e))?;
+  let store = board.store.lock().await;
+
+  Ok((
+    [(header::CACHE_CONTROL, "no-store")],
+    Html::from(
+      html
+        .replace("{{board}}", &name)
+        .replace("{{history}}", store.render_history().into_string().as_str()),
+    ),
+  ))
+}
+
+async fn trains_page(
+  CurrentBoard(board): CurrentBoard,
+  Path(BoardPath { board: name }): Path<BoardPath>,
+) -> Result<impl IntoResponse, AppError> {
+  let html = std::fs::read_to_string("./public/trains.html")
+    .map_err(|e| AppError::Page("trains.html", e))?;
+  let store = board.store.lock().await;
+
+  Ok((
+    [(header::CACHE_CONTROL, "no-store")],
+    Html::from(
+      html
+        .replace("{{board}}", &name)
+        .replace("{{trains}}", store.render_trains().into_string().as_str()),
+    ),
+  ))
 }
 
 #[tokio::main]
@@ -159,190 +492,676 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     .init();
 
   let args = Args::parse();
+  if let Some(path) = &args.stations {
+    let map = dv_tracker::load_map(path).unwrap_or_else(|e| {
+      tracing::error!("failed to load stations from {path:?}:\n{e}");
+      std::process::exit(1);
+    });
+    tracing::info!("loaded {} stations from {path:?}", map.stations.len());
+    dv_tracker::set_map(map)?;
+  }
+  if let Some(path) = &args.locos {
+    let locos = dv_tracker::load_locos(path).unwrap_or_else(|e| {
+      tracing::error!("failed to load locos from {path:?}:\n{e}");
+      std::process::exit(1);
+    });
+    tracing::info!("loaded {} locos from {path:?}", locos.len());
+    dv_tracker::set_locos(locos)?;
+  }
+  std::fs::create_dir_all(&args.data_dir)?;
+
+  let mut names = board::saved_names(&args.data_dir)?;
+  if names.is_empty() {
+    names.push("default".to_string());
+  }
+  let mut boards = BTreeMap::new();
+  for name in names {
+    let board = Board::open(&args.data_dir, &name)?;
+    boards.insert(name, Arc::new(board));
+  }
 
   let app = Router::new()
+    .route("/api/ping", get(async || "pong"))
+    // Nested on its own, since a nested router's fallback is dropped when
+    // the router it's nested in is nested again.
+    .nest("/b/{board}/api/v1", api::router())
     .nest(
-      "/api",
+      "/b/{board}/api",
       Router::new()
-        .route("/ping", get(async || "pong"))
         .route(
           "/order",
-          put(async |State(state): State<AppState>| {
-            if let Ok(mut store) = state.store.try_lock() {
-              store.add(Order::default());
-              Html::from(store.render().into_string())
-            } else {
-              Html::from("Failed to lock orders.".to_string())
-            }
-          }),
+          put(
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>| {
+              let mut store = board.store.lock().await;
+              let order = Order::default();
+              board.commit(&mut store, who, Event::Add { order }).await;
+              Html::from(store.render_view(&view).into_string())
+            },
+          ),
         )
         .route(
           "/order/{guid}",
           delete(
-            async |State(state): State<AppState>, Path(guid): Path<usize>| {
-              if let Ok(mut store) = state.store.try_lock() {
-                store.remove(guid);
-                Html::from(store.render().into_string())
-              } else {
-                Html::from("Failed to lock orders.".to_string())
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Path(GuidPath { guid }): Path<GuidPath>|
+                   -> Result<Html<String>, AppError> {
+              let mut store = board.store.lock().await;
+              store.get(guid).ok_or(AppError::NoSuchOrder)?;
+              board.commit(&mut store, who, Event::Remove { guid }).await;
+              Ok(Html::from(store.render_view(&view).into_string()))
+            },
+          )
+          .post(
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Path(GuidPath { guid }): Path<GuidPath>,
+                   Form(req): Form<OrderRequest>|
+                   -> Result<Html<String>, AppError> {
+              let mut store = board.store.lock().await;
+              let order = store.get(guid).ok_or(AppError::NoSuchOrder)?;
+              let events = req.into_events(guid);
+              check_events(order, &events)?;
+              for event in events {
+                board.commit(&mut store, who, event).await;
               }
+              Ok(Html::from(store.render_view(&view).into_string()))
+            },
+          ),
+        )
+        .route(
+          "/order/{guid}/legs/{destination}",
+          post(
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Path(SidePath { guid, destination }): Path<SidePath>|
+                   -> Result<Html<String>, AppError> {
+              let mut store = board.store.lock().await;
+              let order = store.get(guid).ok_or(AppError::NoSuchOrder)?;
+              let event = Event::AddLeg { guid, destination };
+              order.check_transition(&event)?;
+              board.commit(&mut store, who, event).await;
+              Ok(Html::from(store.render_view(&view).into_string()))
+            },
+          ),
+        )
+        .route(
+          "/order/{guid}/legs/{destination}/{leg}",
+          delete(
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Path(LegPath {
+                     guid,
+                     destination,
+                     leg,
+                   }): Path<LegPath>|
+                   -> Result<Html<String>, AppError> {
+              let mut store = board.store.lock().await;
+              let order = store.get(guid).ok_or(AppError::NoSuchOrder)?;
+              if leg == 0 || order.legs(destination).nth(leg).is_none() {
+                return Err(AppError::NoSuchLeg);
+              }
+              let event = Event::RemoveLeg {
+                guid,
+                destination,
+                leg,
+              };
+              order.check_transition(&event)?;
+              board.commit(&mut store, who, event).await;
+              Ok(Html::from(store.render_view(&view).into_string()))
             },
           )
           .post(
-            async |State(state): State<AppState>,
-                   Path(guid): Path<usize>,
-                   Form(req): Form<OrderRequest>| {
-              if let Ok(mut store) = state.store.try_lock() {
-                if let Some(order) = store.get_mut(guid) {
-                  if let Some(id) = req.id {
-                    order.id = id;
-                  }
-                  if let Some(kind) = req.kind {
-                    order.kind = kind;
-                  }
-                  if let Some(station) = req.from_station {
-                    order.from.station = station;
-                  }
-                  if let Some(yard) = req.from_yard {
-                    order.from.yard = yard;
-                  }
-                  if let Some(track) = req.from_track {
-                    order.from.track = track;
-                  }
-                  if let Some(done) = req.from_done {
-                    order.from.done = done.checked();
-                  }
-                  if let Some(station) = req.to_station {
-                    order.to.station = station;
-                  }
-                  if let Some(yard) = req.to_yard {
-                    order.to.yard = yard;
-                  }
-                  if let Some(track) = req.to_track {
-                    order.to.track = track;
-                  }
-                  if let Some(done) = req.to_done {
-                    order.to.done = done.checked();
-                  }
-                  if let Some(notes) = req.notes {
-                    order.notes = notes;
-                  }
-                  if let Some(tonnes) = req.tonnes {
-                    order.tonnes = tonnes;
-                  }
-                  if let Some(cars) = req.cars {
-                    order.cars = cars;
-                  }
-
-                  order.make_valid();
-                }
-
-                Html::from(store.render().into_string())
-              } else {
-                Html::from("Failed to lock orders.".to_string())
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Path(LegPath {
+                     guid,
+                     destination,
+                     leg,
+                   }): Path<LegPath>,
+                   Form(req): Form<LegRequest>|
+                   -> Result<Html<String>, AppError> {
+              let mut store = board.store.lock().await;
+              let order = store.get(guid).ok_or(AppError::NoSuchOrder)?;
+              if order.legs(destination).nth(leg).is_none() {
+                return Err(AppError::NoSuchLeg);
               }
+              let events = req.into_events(guid, destination, leg);
+              check_events(order, &events)?;
+              for event in events {
+                board.commit(&mut store, who, event).await;
+              }
+              Ok(Html::from(store.render_view(&view).into_string()))
             },
           ),
         )
         .route(
-          "/order/{guid}/duplicate",
+          "/order/{guid}/take",
           post(
-            async |State(state): State<AppState>, Path(guid): Path<usize>| {
-              if let Ok(mut store) = state.store.try_lock() {
-                store.duplicate(guid);
-                Html::from(store.render().into_string())
-              } else {
-                Html::from("Failed to lock orders.".to_string())
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Path(GuidPath { guid }): Path<GuidPath>|
+                   -> Result<Html<String>, AppError> {
+              let mut store = board.store.lock().await;
+              let order = store.get(guid).ok_or(AppError::NoSuchOrder)?;
+              let event = Event::Take {
+                guid,
+                at: Some(unix_time()),
+              };
+              order.check_transition(&event)?;
+              board.commit(&mut store, who, event).await;
+              Ok(Html::from(store.render_view(&view).into_string()))
+            },
+          )
+          .delete(
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Path(GuidPath { guid }): Path<GuidPath>|
+                   -> Result<Html<String>, AppError> {
+              let mut store = board.store.lock().await;
+              let order = store.get(guid).ok_or(AppError::NoSuchOrder)?;
+              let event = Event::Take { guid, at: None };
+              order.check_transition(&event)?;
+              board.commit(&mut store, who, event).await;
+              Ok(Html::from(store.render_view(&view).into_string()))
+            },
+          ),
+        )
+        .route(
+          "/order/{guid}/archive",
+          post(
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Path(GuidPath { guid }): Path<GuidPath>|
+                   -> Result<Html<String>, AppError> {
+              let mut store = board.store.lock().await;
+              let order = store.get(guid).ok_or(AppError::NoSuchOrder)?;
+              if order.status().is_active() {
+                return Err(AppError::NotFinished);
               }
+              board.commit(&mut store, who, Event::Archive { guid }).await;
+              Ok(Html::from(store.render_view(&view).into_string()))
             },
           ),
         )
         .route(
-          "/order/{guid}/move/{direction}",
+          "/order/{guid}/status",
           post(
-            async |State(state): State<AppState>,
-                   Path((guid, direction)): Path<(usize, String)>| {
-              if let Ok(mut store) = state.store.try_lock() {
-                if let Some(pos) =
-                  store.orders.iter().position(|o| o.guid == guid)
-                {
-                  match direction.as_str() {
-                    "up" => {
-                      if pos > 0 {
-                        let item = store.orders.remove(pos);
-                        store.orders.insert(pos - 1, item);
-                      }
-                    }
-                    "down" => {
-                      if pos + 1 < store.orders.len() {
-                        let item = store.orders.remove(pos);
-                        store.orders.insert(pos + 1, item);
-                      }
-                    }
-                    _ => {}
-                  }
-                }
-
-                Html::from(store.render().into_string())
-              } else {
-                Html::from("Failed to lock orders.".to_string())
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Path(GuidPath { guid }): Path<GuidPath>,
+                   Form(req): Form<StatusRequest>|
+                   -> Result<Html<String>, AppError> {
+              let mut store = board.store.lock().await;
+              let order = store.get(guid).ok_or(AppError::NoSuchOrder)?;
+              let event = Event::Close {
+                guid,
+                closed: req.closed(),
+              };
+              order.check_transition(&event)?;
+              board.commit(&mut store, who, event).await;
+              Ok(Html::from(store.render_view(&view).into_string()))
+            },
+          ),
+        )
+        .route(
+          "/order/{guid}/train",
+          post(
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Path(GuidPath { guid }): Path<GuidPath>,
+                   Form(req): Form<TrainRequest>|
+                   -> Result<Html<String>, AppError> {
+              let mut store = board.store.lock().await;
+              store.get(guid).ok_or(AppError::NoSuchOrder)?;
+              let train = req.train()?;
+              if let Some(train) = train {
+                store.train(train).ok_or(AppError::NoSuchTrain)?;
               }
+              let event = Event::AssignTrain { guid, train };
+              board.commit(&mut store, who, event).await;
+              Ok(Html::from(store.render_view(&view).into_string()))
+            },
+          ),
+        )
+        .route(
+          "/trains",
+          get(async |CurrentBoard(board): CurrentBoard| {
+            let store = board.store.lock().await;
+            Html::from(store.render_trains().into_string())
+          })
+          .put(
+            async |CurrentBoard(board): CurrentBoard,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Form(NewTrainRequest { name }): Form<NewTrainRequest>| {
+              let mut store = board.store.lock().await;
+              let name = match name.trim() {
+                "" => format!("Train {}", store.trains.len() + 1),
+                name => name.to_string(),
+              };
+              board.commit(&mut store, who, Event::AddTrain { name }).await;
+              Html::from(store.render_trains().into_string())
+            },
+          ),
+        )
+        .route(
+          "/loco",
+          post(
+            async |CurrentBoard(board): CurrentBoard,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Form(req): Form<LocoRequest>|
+                   -> Result<Html<String>, AppError> {
+              let mut store = board.store.lock().await;
+              let train = TrainRequest { train: req.train }.train()?;
+              if let Some(train) = train {
+                store.train(train).ok_or(AppError::NoSuchTrain)?;
+              }
+              let loco = match req.loco.as_str() {
+                "" => None,
+                code => Some(
+                  dv_tracker::loco(Intern::from_ref(code))
+                    .ok_or(AppError::NoSuchLoco)?
+                    .code,
+                ),
+              };
+              let event = Event::SetLoco { train, loco };
+              board.commit(&mut store, who, event).await;
+              Ok(Html::from(store.render_trains().into_string()))
+            },
+          ),
+        )
+        .route(
+          "/trains/{train}",
+          post(
+            async |CurrentBoard(board): CurrentBoard,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Path(TrainPath { train }): Path<TrainPath>,
+                   Form(edit): Form<TrainEdit>|
+                   -> Result<Html<String>, AppError> {
+              let mut store = board.store.lock().await;
+              store.train(train).ok_or(AppError::NoSuchTrain)?;
+              let event = Event::EditTrain { train, edit };
+              board.commit(&mut store, who, event).await;
+              Ok(Html::from(store.render_trains().into_string()))
+            },
+          )
+          .delete(
+            async |CurrentBoard(board): CurrentBoard,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Path(TrainPath { train }): Path<TrainPath>|
+                   -> Result<Html<String>, AppError> {
+              let mut store = board.store.lock().await;
+              store.train(train).ok_or(AppError::NoSuchTrain)?;
+              board.commit(&mut store, who, Event::RemoveTrain { train }).await;
+              Ok(Html::from(store.render_trains().into_string()))
+            },
+          ),
+        )
+        .route(
+          "/order/{guid}/duplicate",
+          post(
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Path(GuidPath { guid }): Path<GuidPath>|
+                   -> Result<Html<String>, AppError> {
+              let mut store = board.store.lock().await;
+              store.get(guid).ok_or(AppError::NoSuchOrder)?;
+              board.commit(&mut store, who, Event::Duplicate { guid }).await;
+              Ok(Html::from(store.render_view(&view).into_string()))
+            },
+          ),
+        )
+        .route(
+          "/order/{guid}/move/{direction}",
+          post(
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Path(MovePath { guid, direction }): Path<MovePath>|
+                   -> Result<Html<String>, AppError> {
+              let mut store = board.store.lock().await;
+              store.get(guid).ok_or(AppError::NoSuchOrder)?;
+              let event = Event::Move { guid, direction };
+              board.commit(&mut store, who, event).await;
+              Ok(Html::from(store.render_view(&view).into_string()))
             },
           ),
         )
         .route(
           "/parse-order",
           put(
-            async |State(state): State<AppState>,
-                   Form(req): Form<ManualOrderRequest>| {
-              if let Ok(mut store) = state.store.try_lock() {
-                if let Ok(result) = Order::parse(req.order) {
-                  store.add(result);
-                  Html::from(store.render().into_string())
-                } else {
-                  Html::from("Failed to parse order.".to_string())
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Form(req): Form<ManualOrderRequest>|
+                   -> Result<Html<String>, AppError> {
+              let input = req.order;
+              let order =
+                Order::parse(&input).map_err(|error| AppError::Parse {
+                  input: input.clone(),
+                  error,
+                })?;
+              let mut store = board.store.lock().await;
+              let event = Event::Parse { input, order };
+              board.commit(&mut store, who, event).await;
+              Ok(Html::from(store.render_view(&view).into_string()))
+            },
+          ),
+        )
+        .route(
+          "/parse-orders",
+          put(
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Form(req): Form<BulkOrderRequest>| {
+              let mut store = board.store.lock().await;
+              let mut orders = Vec::new();
+              let mut failed = Vec::new();
+              for (line, input, result) in Order::parse_lines(&req.orders) {
+                match result {
+                  Ok(order) => orders.push(Parsed {
+                    input: input.trim().to_string(),
+                    order,
+                  }),
+                  Err(error) => failed.push((line, input, error)),
                 }
-              } else {
-                Html::from("Failed to lock orders.".to_string())
               }
+              // One event for the lot, so a single undo takes it back out.
+              let added = orders.len();
+              if added > 0 {
+                board.commit(&mut store, who, Event::ParseAll { orders }).await;
+              }
+
+              // The orders go along out of band, since the form is what gets
+              // swapped. Table rows only survive parsing inside a template.
+              let rows = store.render_view(&view);
+              Html::from(
+                html!(
+                  (render_bulk_form(added, &failed))
+                  template {
+                    tbody #orders hx-swap-oob="innerHTML" { (rows) }
+                  }
+                )
+                .into_string(),
+              )
             },
           ),
         )
+        .route(
+          "/undo",
+          post(
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>| {
+              let mut store = board.store.lock().await;
+              if store.can_undo() {
+                board.commit(&mut store, who, Event::Undo).await;
+              }
+              Html::from(store.render_view(&view).into_string())
+            },
+          ),
+        )
+        .route(
+          "/redo",
+          post(
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>| {
+              let mut store = board.store.lock().await;
+              if store.can_redo() {
+                board.commit(&mut store, who, Event::Redo).await;
+              }
+              Html::from(store.render_view(&view).into_string())
+            },
+          ),
+        )
+        .route(
+          "/events",
+          get(
+            async |CurrentBoard(board): CurrentBoard, uri: Uri| {
+              let view = view_of(&uri);
+              // A lagging client skips straight to the newest board, since
+              // every update contains all of the orders anyway. Clients with
+              // a view of their own get the board rendered for it instead.
+              let stream = BroadcastStream::new(board.updates.subscribe())
+                .filter_map(|orders| orders.ok())
+                .then(move |orders| {
+                  let board = board.clone();
+                  let view = view.clone();
+                  async move {
+                    if view.is_empty() {
+                      return orders;
+                    }
+                    let store = board.store.lock().await;
+                    store.render_view(&view).into_string()
+                  }
+                })
+                .map(|orders| {
+                  Ok::<_, Infallible>(
+                    sse::Event::default().event("orders").data(orders),
+                  )
+                });
+
+              Sse::new(stream).keep_alive(KeepAlive::default())
+            },
+          ),
+        )
+        .route(
+          "/orders.txt",
+          get(async |CurrentBoard(board): CurrentBoard| {
+            let store = board.store.lock().await;
+            store
+              .orders
+              .iter()
+              .map(|order| format!("{order}\n"))
+              .collect::<String>()
+          }),
+        )
         .route(
           "/orders",
-          get(async |State(state): State<AppState>| {
-            if let Ok(store) = state.store.try_lock() {
-              Html::from(store.render().into_string())
-            } else {
-              Html::from("Failed to lock orders.".to_string())
-            }
+          get(
+            async |CurrentBoard(board): CurrentBoard,
+                   Query(view): Query<OrderView>| {
+              let store = board.store.lock().await;
+              Html::from(store.render_view(&view).into_string())
+            },
+          ),
+        )
+        .route(
+          "/plan",
+          get(
+            async |CurrentBoard(board): CurrentBoard,
+                   Query(req): Query<PlanRequest>|
+                   -> Result<Html<String>, AppError> {
+              let plan =
+                board.plan(req.start()).await.map_err(AppError::Plan)?;
+              Ok(Html::from(plan.render().into_string()))
+            },
+          ),
+        )
+        .route(
+          "/history",
+          get(async |CurrentBoard(board): CurrentBoard| {
+            let store = board.store.lock().await;
+            Html::from(store.render_history().into_string())
+          }),
+        )
+        .route(
+          "/history/{batch}/restore",
+          post(
+            async |CurrentBoard(board): CurrentBoard,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>,
+                   Path(BatchPath { batch }): Path<BatchPath>|
+                   -> Result<Html<String>, AppError> {
+              let mut store = board.store.lock().await;
+              if store.batch_len(batch) == 0 {
+                return Err(AppError::NoSuchBatch);
+              }
+              board.commit(&mut store, who, Event::Restore { batch }).await;
+              Ok(Html::from(store.render_history().into_string()))
+            },
+          ),
+        )
+        .route(
+          "/archive-finished",
+          post(
+            async |CurrentBoard(board): CurrentBoard,
+                   CurrentView(view): CurrentView,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>| {
+              let mut store = board.store.lock().await;
+              board.commit(&mut store, who, Event::ArchiveFinished).await;
+              Html::from(store.render_view(&view).into_string())
+            },
+          ),
+        )
+        .route(
+          "/sessions",
+          post(
+            async |CurrentBoard(board): CurrentBoard,
+                   ConnectInfo(who): ConnectInfo<SocketAddr>| {
+              let mut store = board.store.lock().await;
+              board.commit(&mut store, who, Event::NewSession).await;
+              Html::from(store.render_history().into_string())
+            },
+          ),
+        )
+        .route(
+          "/tracks",
+          get(async |CurrentBoard(board): CurrentBoard| {
+            let store = board.store.lock().await;
+            Html::from(store.render_occupancy().into_string())
           }),
         ),
     )
     .route(
       "/",
-      get(async |State(state): State<AppState>| {
-        if let Ok(html) = std::fs::read_to_string("./public/index.html") {
-          if let Ok(store) = state.store.try_lock() {
-            (
-              [(header::CACHE_CONTROL, "no-store")],
-              Html::from(
-                html
-                  .replace("{{orders}}", store.render().into_string().as_str()),
-              ),
-            )
-          } else {
-            (
-              [(header::CACHE_CONTROL, "no-store")],
-              Html::from("Failed to lock orders.".to_string()),
-            )
-          }
-        } else {
-          (
+      get(
+        async |State(state): State<AppState>| -> Result<_, AppError> {
+          let html = std::fs::read_to_string("./public/boards.html")
+            .map_err(|e| AppError::Page("boards.html", e))?;
+
+          Ok((
             [(header::CACHE_CONTROL, "no-store")],
-            Html::from("Failed to read index.html.".to_string()),
-          )
-        }
+            Html::from(
+              html.replace("{{boards}}", &state.render_boards(None).await.0),
+            ),
+          ))
+        },
+      ),
+    )
+    .route(
+      "/boards",
+      post(
+        async |State(state): State<AppState>, Form(req): Form<BoardRequest>| {
+          if !board::is_valid_name(&req.name) {
+            return state.render_boards(Some(INVALID_NAME)).await;
+          }
+
+          let mut boards = state.boards.lock().await;
+          if boards.contains_key(&req.name) {
+            drop(boards);
+            return state.render_boards(Some(NAME_TAKEN)).await;
+          }
+
+          match Board::open(&state.data_dir, &req.name) {
+            Ok(board) => {
+              boards.insert(req.name, Arc::new(board));
+            }
+            Err(e) => {
+              tracing::error!("failed to create board {:?}: {e}", req.name);
+              drop(boards);
+              return state
+                .render_boards(Some("Failed to create board."))
+                .await;
+            }
+          }
+          drop(boards);
+
+          state.render_boards(None).await
+        },
+      ),
+    )
+    .route(
+      "/boards/{board}",
+      delete(
+        async |State(state): State<AppState>,
+               Path(BoardPath { board: name }): Path<BoardPath>| {
+          let removed = state.boards.lock().await.remove(&name);
+
+          if let Some(board) = removed
+            && let Err(e) = board.delete().await
+          {
+            tracing::error!("failed to delete board {name:?}: {e}");
+            return state
+              .render_boards(Some("Failed to delete board files."))
+              .await;
+          }
+
+          state.render_boards(None).await
+        },
+      ),
+    )
+    .route(
+      "/boards/{board}/rename",
+      post(
+        async |State(state): State<AppState>,
+               Path(BoardPath { board: name }): Path<BoardPath>,
+               Form(req): Form<BoardRequest>| {
+          if !board::is_valid_name(&req.name) {
+            return state.render_boards(Some(INVALID_NAME)).await;
+          }
+
+          let mut boards = state.boards.lock().await;
+          if req.name != name {
+            if boards.contains_key(&req.name) {
+              drop(boards);
+              return state.render_boards(Some(NAME_TAKEN)).await;
+            }
+
+            if let Some(board) = boards.remove(&name) {
+              if let Err(e) = board.rename(&state.data_dir, &req.name).await {
+                tracing::error!("failed to rename board {name:?}: {e}");
+                boards.insert(name, board);
+                drop(boards);
+                return state
+                  .render_boards(Some("Failed to rename board."))
+                  .await;
+              }
+              boards.insert(req.name, board);
+            }
+          }
+          drop(boards);
+
+          state.render_boards(None).await
+        },
+      ),
+    )
+    .route("/b/{board}", get(board_page))
+    .route("/b/{board}/", get(board_page))
+    .route("/b/{board}/tracks", get(tracks_page))
+    .route("/b/{board}/history", get(history_page))
+    .route("/b/{board}/trains", get(trains_page))
+    .route(
+      "/colors.css",
+      get(async || {
+        (
+          [(header::CONTENT_TYPE, "text/css")],
+          dv_tracker::render_css(),
+        )
       }),
     )
     .route(
@@ -370,10 +1189,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     .layer(CorsLayer::permissive())
     .fallback_service(ServeDir::new("./public"))
     .layer(TraceLayer::new_for_http())
-    .with_state(AppState::new());
+    .with_state(AppState::new(boards, args.data_dir));
 
   let listener = TcpListener::bind(args.address.as_slice()).await?;
-  axum::serve(listener, app).await?;
+  axum::serve(
+    listener,
+    app.into_make_service_with_connect_info::<SocketAddr>(),
+  )
+  .await?;
 
   Ok(())
 }
@@ -0,0 +1,258 @@
+use std::ops::Range;
+
+use maud::{Markup, html};
+
+/// An error in an order string, along with the columns it covers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+  pub message: String,
+  /// Character columns of the offending text, starting from zero.
+  pub span: Range<usize>,
+}
+
+impl std::fmt::Display for ParseError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.span.len() > 1 {
+      write!(
+        f,
+        "{} (columns {}-{})",
+        self.message,
+        self.span.start + 1,
+        self.span.end
+      )
+    } else {
+      write!(f, "{} (column {})", self.message, self.span.start + 1)
+    }
+  }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+  /// Renders the message with the offending part of `input` highlighted.
+  pub fn render(&self, input: &str) -> Markup {
+    let chars: Vec<char> = input.chars().collect();
+    let slice = |range: Range<usize>| -> String {
+      chars[range.start.min(chars.len())..range.end.min(chars.len())]
+        .iter()
+        .collect()
+    };
+    let marked = slice(self.span.clone());
+
+    html!(
+      (self.to_string())
+      br;
+      code {
+        (slice(0..self.span.start))
+        mark {
+          @if marked.is_empty() { " " } @else { (marked) }
+        }
+        (slice(self.span.end..chars.len()))
+      }
+    )
+  }
+}
+
+/// A whitespace-separated word in an order string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token<'a> {
+  pub text: &'a str,
+  pub span: Range<usize>,
+}
+
+impl<'a> Token<'a> {
+  pub fn error(&self, message: impl Into<String>) -> ParseError {
+    ParseError {
+      message: message.into(),
+      span: self.span.clone(),
+    }
+  }
+
+  /// Splits the token before its first digit, e.g. `FH01` into `FH` and `01`.
+  pub fn split_at_digit(&self) -> (Token<'a>, Token<'a>) {
+    let at = self
+      .text
+      .find(|c: char| c.is_ascii_digit())
+      .unwrap_or(self.text.len());
+    let (head, tail) = self.text.split_at(at);
+    let mid = self.span.start + head.chars().count();
+
+    (
+      Token {
+        text: head,
+        span: self.span.start..mid,
+      },
+      Token {
+        text: tail,
+        span: mid..self.span.end,
+      },
+    )
+  }
+
+  /// Splits the token at the first `c`, dropping it, e.g. `A1:3` into `A1` and
+  /// `3`.
+  pub fn split_once(&self, c: char) -> (Token<'a>, Option<Token<'a>>) {
+    let Some((head, tail)) = self.text.split_once(c) else {
+      return (self.clone(), None);
+    };
+    let mid = self.span.start + head.chars().count();
+
+    (
+      Token {
+        text: head,
+        span: self.span.start..mid,
+      },
+      Some(Token {
+        text: tail,
+        span: mid + 1..self.span.end,
+      }),
+    )
+  }
+
+  /// An empty token has nowhere to point, so errors about it point at the
+  /// column just after where it should have been.
+  pub fn error_if_empty(&self, message: &str) -> Result<(), ParseError> {
+    if self.text.is_empty() {
+      Err(ParseError {
+        message: message.to_string(),
+        span: self.span.start..self.span.start + 1,
+      })
+    } else {
+      Ok(())
+    }
+  }
+}
+
+/// The words of an order string, consumed from left to right.
+pub struct Tokens<'a> {
+  tokens: std::iter::Peekable<std::vec::IntoIter<Token<'a>>>,
+  /// The column just past the end of the input.
+  end: usize,
+}
+
+impl<'a> Tokens<'a> {
+  pub fn new(input: &'a str) -> Self {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut column = 0;
+
+    for (i, (byte, c)) in input.char_indices().enumerate() {
+      column = i + 1;
+      match (c.is_whitespace(), start) {
+        (false, None) => start = Some((byte, i)),
+        (true, Some((start_byte, start_column))) => {
+          tokens.push(Token {
+            text: &input[start_byte..byte],
+            span: start_column..i,
+          });
+          start = None;
+        }
+        _ => {}
+      }
+    }
+    if let Some((start_byte, start_column)) = start {
+      tokens.push(Token {
+        text: &input[start_byte..],
+        span: start_column..column,
+      });
+    }
+
+    Self {
+      tokens: tokens.into_iter().peekable(),
+      end: column,
+    }
+  }
+
+  /// Takes the next token, or fails with "missing `what`".
+  pub fn next(&mut self, what: &str) -> Result<Token<'a>, ParseError> {
+    self.tokens.next().ok_or_else(|| ParseError {
+      message: format!("missing {what}"),
+      span: self.end..self.end + 1,
+    })
+  }
+
+  pub fn next_optional(&mut self) -> Option<Token<'a>> {
+    self.tokens.next()
+  }
+
+  /// Takes the next token if it is exactly `text`.
+  pub fn next_if(&mut self, text: &str) -> bool {
+    self.tokens.next_if(|token| token.text == text).is_some()
+  }
+
+  /// Fails if there are tokens left over.
+  pub fn finish(mut self) -> Result<(), ParseError> {
+    match self.tokens.next() {
+      Some(token) => Err(token.error(format!("unexpected `{}`", token.text))),
+      None => Ok(()),
+    }
+  }
+}
+
+/// Quotes order notes so they fit on one line, escaping `"`, `\` and line
+/// breaks. The inverse of [`split_notes`].
+pub fn quote_notes(notes: &str) -> String {
+  let mut quoted = String::with_capacity(notes.len() + 2);
+  quoted.push('"');
+  for c in notes.chars() {
+    match c {
+      '"' => quoted.push_str("\\\""),
+      '\\' => quoted.push_str("\\\\"),
+      '\n' => quoted.push_str("\\n"),
+      '\r' => quoted.push_str("\\r"),
+      '\t' => quoted.push_str("\\t"),
+      c => quoted.push(c),
+    }
+  }
+  quoted.push('"');
+  quoted
+}
+
+/// Splits an order string into the words before its quoted notes and the
+/// notes themselves, unescaped. Input without notes gives empty notes.
+pub fn split_notes(input: &str) -> Result<(&str, String), ParseError> {
+  let Some(quote) = input.find('"') else {
+    return Ok((input, String::new()));
+  };
+  let (head, rest) = input.split_at(quote);
+  let start = head.chars().count();
+
+  let mut notes = String::new();
+  let mut chars = rest.chars().enumerate().skip(1);
+  while let Some((i, c)) = chars.next() {
+    match c {
+      '"' => {
+        return match rest.chars().skip(i + 1).position(|c| !c.is_whitespace()) {
+          Some(j) => {
+            let column = start + i + 1 + j;
+            Err(ParseError {
+              message: "unexpected text after notes".to_string(),
+              span: column..start + rest.chars().count(),
+            })
+          }
+          None => Ok((head, notes)),
+        };
+      }
+      '\\' => match chars.next() {
+        Some((_, '"')) => notes.push('"'),
+        Some((_, '\\')) => notes.push('\\'),
+        Some((_, 'n')) => notes.push('\n'),
+        Some((_, 'r')) => notes.push('\r'),
+        Some((_, 't')) => notes.push('\t'),
+        Some((j, c)) => {
+          return Err(ParseError {
+            message: format!("unknown escape `\\{c}` in notes"),
+            span: start + i..start + j + 1,
+          });
+        }
+        None => break,
+      },
+      c => notes.push(c),
+    }
+  }
+
+  Err(ParseError {
+    message: "notes are missing their closing `\"`".to_string(),
+    span: start..start + 1,
+  })
+}
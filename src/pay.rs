@@ -0,0 +1,73 @@
+use crate::{Order, OrderStatus, OrderStore};
+
+impl Order {
+  /// When the bonus runs out, in seconds since the Unix epoch, if the job has
+  /// been taken and has a bonus time. A deadline past the end of time is as
+  /// good as none.
+  pub fn deadline(&self) -> Option<u64> {
+    let taken_at = self.taken_at?;
+    if self.bonus_minutes == 0 {
+      return None;
+    }
+    taken_at.checked_add(u64::from(self.bonus_minutes) * 60)
+  }
+
+  /// Seconds left on the bonus timer at `now`, negative once it has run out.
+  /// Delivered and closed orders have nothing left to time.
+  pub fn time_left(&self, now: u64) -> Option<i64> {
+    let deadline = i64::try_from(self.deadline()?).ok()?;
+    let now = i64::try_from(now).unwrap_or(i64::MAX);
+    self
+      .status()
+      .is_active()
+      .then(|| deadline.saturating_sub(now))
+  }
+
+  /// The bonus is lost once the timer runs out before the order is finished.
+  pub fn bonus_lost(&self, now: u64) -> bool {
+    self
+      .deadline()
+      .is_some_and(|deadline| self.finished_at.unwrap_or(now) > deadline)
+  }
+
+  /// What the order pays at `now`, or paid if it's finished. Abandoned
+  /// orders pay nothing.
+  pub fn earnings(&self, now: u64) -> u64 {
+    if self.status() == OrderStatus::Abandoned {
+      return 0;
+    }
+    let bonus = if self.bonus_lost(now) { 0 } else { self.bonus };
+    u64::from(self.payout) + u64::from(bonus)
+  }
+}
+
+/// Formats seconds as `m:ss`, or `h:mm:ss` from an hour up, with a leading `-`
+/// once they are negative. The countdown script in `index.html` matches this.
+pub fn format_time_left(seconds: i64) -> String {
+  let sign = if seconds < 0 { "-" } else { "" };
+  let seconds = seconds.unsigned_abs();
+  let (h, m, s) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+  if h > 0 {
+    format!("{sign}{h}:{m:02}:{s:02}")
+  } else {
+    format!("{sign}{m}:{s:02}")
+  }
+}
+
+impl OrderStore {
+  /// What the board pays at `now`: base pay for every order, plus the bonus
+  /// for those that haven't lost it.
+  pub fn expected_payout(&self, now: u64) -> u64 {
+    self.orders.iter().map(|o| o.earnings(now)).sum()
+  }
+
+  /// The bonus on unfinished orders whose timer is still running.
+  pub fn bonus_at_risk(&self, now: u64) -> u64 {
+    self
+      .orders
+      .iter()
+      .filter(|o| o.time_left(now).is_some_and(|left| left >= 0))
+      .map(|o| u64::from(o.bonus))
+      .sum()
+  }
+}
@@ -0,0 +1,251 @@
+use internment::Intern;
+use maud::{Markup, html};
+use serde::Serialize;
+
+use crate::{Destination, DestinationKind, Map, Order};
+
+/// What happens at a stop.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+  Pickup,
+  Dropoff,
+}
+
+impl std::fmt::Display for Action {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Action::Pickup => write!(f, "Pick up"),
+      Action::Dropoff => write!(f, "Drop off"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Stop {
+  pub action: Action,
+  /// The order's `guid`.
+  pub guid: usize,
+  /// The order, as an order string.
+  pub order: String,
+  pub at: Destination,
+  /// Distance travelled from the previous stop.
+  pub km: f32,
+}
+
+/// An itinerary covering every unfinished pickup and dropoff.
+#[derive(Debug, Clone, Serialize)]
+pub struct Plan {
+  pub start: Intern<String>,
+  pub stops: Vec<Stop>,
+  pub km: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlanError {
+  /// The map has no connections between stations.
+  NoConnections,
+  UnknownStart(Intern<String>),
+}
+
+impl std::fmt::Display for PlanError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      PlanError::NoConnections => {
+        write!(
+          f,
+          "the map has no connections between stations to plan with"
+        )
+      }
+      PlanError::UnknownStart(station) => {
+        write!(f, "unknown start station `{station}`")
+      }
+    }
+  }
+}
+
+impl std::error::Error for PlanError {}
+
+/// How many times the planner goes over the whole route looking for stops to
+/// move. Each pass tries every stop in every position, so this keeps a long
+/// board from taking ages; most routes settle within a few passes anyway.
+const MAX_PASSES: usize = 8;
+
+/// A stop that hasn't been placed yet. `after` holds the indices of the
+/// pickups that have to come first, for dropoffs whose cars haven't all been
+/// picked up.
+struct Pending<'a> {
+  action: Action,
+  order: &'a Order,
+  at: &'a Destination,
+  after: Vec<usize>,
+}
+
+/// Plans a route through the unfinished orders, starting at `start` or, if
+/// there isn't one, at the first stop on the board.
+///
+/// The route is built greedily, always heading to the nearest stop that can be
+/// made next, and then improved by moving single stops around for as long as
+/// that makes it shorter, up to `MAX_PASSES` times over. Cars are always
+/// picked up before they are dropped off. Stops at the same station cost
+/// nothing, so they end up together.
+pub fn plan(
+  map: &Map,
+  orders: &[Order],
+  start: Option<Intern<String>>,
+) -> Result<Plan, PlanError> {
+  if map.connections.is_empty() {
+    return Err(PlanError::NoConnections);
+  }
+  if let Some(start) = start
+    && !map.stations.iter().any(|s| s.short == start)
+  {
+    return Err(PlanError::UnknownStart(start));
+  }
+
+  let mut pending = Vec::new();
+  // Completed and abandoned orders can have legs left unchecked, but nobody
+  // is going to drive to them.
+  for order in orders.iter().filter(|o| o.status().is_active()) {
+    let mut pickups = Vec::new();
+    for at in order.legs(DestinationKind::From).filter(|leg| !leg.done) {
+      pickups.push(pending.len());
+      pending.push(Pending {
+        action: Action::Pickup,
+        order,
+        at,
+        after: Vec::new(),
+      });
+    }
+    for at in order.legs(DestinationKind::To).filter(|leg| !leg.done) {
+      pending.push(Pending {
+        action: Action::Dropoff,
+        order,
+        at,
+        after: pickups.clone(),
+      });
+    }
+  }
+
+  // With nothing to do, the plan stays wherever it was asked to start.
+  let Some(first) = pending.first() else {
+    return Ok(Plan {
+      start: start.unwrap_or(map.stations[0].short),
+      stops: Vec::new(),
+      km: 0.0,
+    });
+  };
+  let start = start.unwrap_or(first.at.station);
+  // Every station is reachable, since maps with connections are checked for
+  // that when they're loaded.
+  let distance = |a: Intern<String>, b: Intern<String>| {
+    map.distance(a, b).unwrap_or(f32::INFINITY)
+  };
+  let length = |route: &[usize]| {
+    let mut at = start;
+    let mut km = 0.0;
+    for &i in route {
+      km += distance(at, pending[i].at.station);
+      at = pending[i].at.station;
+    }
+    km
+  };
+  let is_valid = |route: &[usize]| {
+    route.iter().enumerate().all(|(position, &i)| {
+      pending[i]
+        .after
+        .iter()
+        .all(|pickup| route[..position].contains(pickup))
+    })
+  };
+
+  let mut route = Vec::with_capacity(pending.len());
+  let mut at = start;
+  while route.len() < pending.len() {
+    let next = (0..pending.len())
+      .filter(|i| !route.contains(i))
+      .filter(|i| pending[*i].after.iter().all(|p| route.contains(p)))
+      .min_by(|a, b| {
+        distance(at, pending[*a].at.station)
+          .total_cmp(&distance(at, pending[*b].at.station))
+      })
+      .expect("a pickup always comes before its dropoff");
+    route.push(next);
+    at = pending[next].at.station;
+  }
+
+  let mut best = length(&route);
+  for _ in 0..MAX_PASSES {
+    let mut improved = false;
+    for from in 0..route.len() {
+      for to in 0..route.len() {
+        if from == to {
+          continue;
+        }
+        let mut candidate = route.clone();
+        let stop = candidate.remove(from);
+        candidate.insert(to, stop);
+        let km = length(&candidate);
+        // Guard against float noise looping forever.
+        if km < best - 1e-3 && is_valid(&candidate) {
+          route = candidate;
+          best = km;
+          improved = true;
+        }
+      }
+    }
+    if !improved {
+      break;
+    }
+  }
+
+  let mut at = start;
+  let stops = route
+    .into_iter()
+    .map(|i| {
+      let Pending {
+        action,
+        order,
+        at: dest,
+        ..
+      } = &pending[i];
+      let km = distance(at, dest.station);
+      at = dest.station;
+      Stop {
+        action: *action,
+        guid: order.guid,
+        order: order.to_string(),
+        at: (*dest).clone(),
+        km,
+      }
+    })
+    .collect();
+
+  Ok(Plan {
+    start,
+    stops,
+    km: best,
+  })
+}
+
+impl Plan {
+  pub fn render(&self) -> Markup {
+    html!(
+      @if self.stops.is_empty() {
+        p { "Nothing left to pick up or drop off." }
+      } @else {
+        p { "Starting at " (self.start) ", " (format!("{:.1}", self.km)) " km in total:" }
+        ol {
+          @for stop in &self.stops {
+            li {
+              (stop.action) " at " b { (stop.at) } ": " (stop.order)
+              @if stop.km > 0.0 {
+                " (" (format!("{:.1}", stop.km)) " km)"
+              }
+            }
+          }
+        }
+      }
+    )
+  }
+}
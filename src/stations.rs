@@ -0,0 +1,373 @@
+use std::{
+  collections::{HashMap, HashSet},
+  fs, io,
+  path::Path,
+  sync::OnceLock,
+};
+
+use internment::Intern;
+use serde::Deserialize;
+
+/// The map that ships with the tracker.
+const BUILT_IN: &str = include_str!("../stations.toml");
+
+static MAP: OnceLock<Map> = OnceLock::new();
+
+/// The stations, and the lines between them.
+#[derive(Debug, Clone)]
+pub struct Map {
+  pub stations: Vec<Station>,
+  pub connections: Vec<Connection>,
+  /// The grade of connections that don't give their own, in percent.
+  pub default_grade: f32,
+  /// Shortest distances in km between every pair of stations, indexed like
+  /// `stations`.
+  distances: Vec<Vec<f32>>,
+  /// The next station on the shortest path between every pair of stations,
+  /// indexed like `distances`.
+  next: Vec<Vec<Option<usize>>>,
+}
+
+/// A stretch of line between two neighbouring stations.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Connection {
+  pub between: [Intern<String>; 2],
+  pub km: f32,
+  /// The steepest grade along the connection, in percent.
+  #[serde(default)]
+  pub grade: Option<f32>,
+}
+
+impl Map {
+  /// The shortest distance in km between two stations by rail, if both exist
+  /// and are connected.
+  pub fn distance(
+    &self,
+    from: Intern<String>,
+    to: Intern<String>,
+  ) -> Option<f32> {
+    let index = |station| self.stations.iter().position(|s| s.short == station);
+    let km = self.distances[index(from)?][index(to)?];
+    km.is_finite().then_some(km)
+  }
+
+  /// The steepest grade in percent on the shortest path between two
+  /// stations, if both exist and are connected.
+  pub fn grade(&self, from: Intern<String>, to: Intern<String>) -> Option<f32> {
+    let index = |station| self.stations.iter().position(|s| s.short == station);
+    let (mut at, to) = (index(from)?, index(to)?);
+
+    let mut grade = 0.0_f32;
+    while at != to {
+      let next = self.next[at][to]?;
+      let between = [self.stations[at].short, self.stations[next].short];
+      grade = self
+        .connections
+        .iter()
+        .filter(|c| {
+          c.between == between || c.between == [between[1], between[0]]
+        })
+        .map(|c| c.grade.unwrap_or(self.default_grade))
+        .fold(grade, f32::max);
+      at = next;
+    }
+
+    Some(grade)
+  }
+
+  /// Floyd-Warshall over the connections. Maps are small enough that this is
+  /// instant.
+  fn compute_distances(&mut self) {
+    let n = self.stations.len();
+    let index = |station| self.stations.iter().position(|s| s.short == station);
+
+    let mut km = vec![vec![f32::INFINITY; n]; n];
+    let mut next = vec![vec![None; n]; n];
+    for (i, row) in km.iter_mut().enumerate() {
+      row[i] = 0.0;
+    }
+    for connection in &self.connections {
+      let [a, b] = connection.between.map(|s| index(s).unwrap_or_default());
+      if connection.km < km[a][b] {
+        km[a][b] = connection.km;
+        km[b][a] = connection.km;
+        next[a][b] = Some(b);
+        next[b][a] = Some(a);
+      }
+    }
+    for k in 0..n {
+      for i in 0..n {
+        for j in 0..n {
+          if km[i][k] + km[k][j] < km[i][j] {
+            km[i][j] = km[i][k] + km[k][j];
+            next[i][j] = next[i][k];
+          }
+        }
+      }
+    }
+
+    self.distances = km;
+    self.next = next;
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Station {
+  pub short: Intern<String>,
+  pub long: Intern<String>,
+  /// A CSS colour for the station's cells, e.g. `#7b8294`.
+  pub color: String,
+  pub tracks: HashMap<Intern<String>, Vec<u8>>,
+  /// How many cars fit on a yard's track, for the tracks where it's known.
+  pub capacity: HashMap<(Intern<String>, u8), u16>,
+}
+
+impl Station {
+  pub fn capacity(&self, yard: Intern<String>, track: u8) -> Option<u16> {
+    self.capacity.get(&(yard, track)).copied()
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StationsFile {
+  station: Vec<StationEntry>,
+  #[serde(default)]
+  connection: Vec<Connection>,
+  #[serde(default)]
+  default_grade: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StationEntry {
+  short: Intern<String>,
+  long: Intern<String>,
+  color: String,
+  tracks: HashMap<Intern<String>, Vec<TrackEntry>>,
+}
+
+/// A track is either just its number, or its number and how many cars fit on
+/// it: `1` or `{ track = 1, cars = 12 }`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TrackEntry {
+  Number(u8),
+  WithCapacity { track: u8, cars: u16 },
+}
+
+impl From<StationEntry> for Station {
+  fn from(entry: StationEntry) -> Self {
+    let mut capacity = HashMap::new();
+    let tracks = entry
+      .tracks
+      .into_iter()
+      .map(|(yard, tracks)| {
+        let tracks = tracks
+          .into_iter()
+          .map(|track| match track {
+            TrackEntry::Number(track) => track,
+            TrackEntry::WithCapacity { track, cars } => {
+              capacity.insert((yard, track), cars);
+              track
+            }
+          })
+          .collect();
+        (yard, tracks)
+      })
+      .collect();
+
+    Self {
+      short: entry.short,
+      long: entry.long,
+      color: entry.color,
+      tracks,
+      capacity,
+    }
+  }
+}
+
+/// Why a stations file couldn't be loaded.
+#[derive(Debug)]
+pub enum StationsError {
+  Io(io::Error),
+  Toml(toml::de::Error),
+  /// The file parsed, but doesn't describe a usable map.
+  Invalid(String),
+}
+
+impl std::fmt::Display for StationsError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      StationsError::Io(e) => write!(f, "{e}"),
+      StationsError::Toml(e) => write!(f, "{e}"),
+      StationsError::Invalid(message) => write!(f, "{message}"),
+    }
+  }
+}
+
+impl std::error::Error for StationsError {}
+
+/// The map: the one passed to [`set_map`], or the built-in one if it was never
+/// called.
+pub fn map() -> &'static Map {
+  MAP.get_or_init(|| {
+    parse_map(BUILT_IN).expect("built-in stations.toml is valid")
+  })
+}
+
+/// The stations on the [`map`].
+pub fn stations() -> &'static [Station] {
+  &map().stations
+}
+
+/// Replaces the built-in map. This can only happen once, before anything has
+/// looked at the map, so it belongs at the very start of the program.
+pub fn set_map(map: Map) -> Result<(), StationsError> {
+  MAP.set(map).map_err(|_| {
+    StationsError::Invalid("the stations have already been loaded".to_string())
+  })
+}
+
+/// Reads and validates a stations file.
+pub fn load_map(path: impl AsRef<Path>) -> Result<Map, StationsError> {
+  let contents = fs::read_to_string(path).map_err(StationsError::Io)?;
+  parse_map(&contents)
+}
+
+/// Parses and validates a map in the format of `stations.toml`. Track lists
+/// are sorted, so the first track of a yard is always the lowest.
+pub fn parse_map(toml: &str) -> Result<Map, StationsError> {
+  let file =
+    toml::from_str::<StationsFile>(toml).map_err(StationsError::Toml)?;
+  let mut stations: Vec<Station> =
+    file.station.into_iter().map(Station::from).collect();
+  let invalid = |message: String| Err(StationsError::Invalid(message));
+
+  if stations.is_empty() {
+    return invalid("there are no stations".to_string());
+  }
+
+  let mut seen = HashSet::new();
+  for station in &mut stations {
+    let short = station.short;
+    // Order strings are upper-cased and split at the first digit of the
+    // yard/track, so codes and yards can't contain anything else.
+    // They also end up as CSS classes, so can't start with a digit.
+    if !short.starts_with(|c: char| c.is_ascii_uppercase())
+      || !short
+        .chars()
+        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+    {
+      return invalid(format!(
+        "station code `{short}` must be upper-case letters and digits, \
+         starting with a letter"
+      ));
+    }
+    if !seen.insert(short) {
+      return invalid(format!("station `{short}` is listed twice"));
+    }
+    if !is_hex_color(&station.color) {
+      return invalid(format!(
+        "station {short} has colour `{}`, expected e.g. #7b8294",
+        station.color
+      ));
+    }
+    if station.tracks.is_empty() {
+      return invalid(format!("station {short} has no yards"));
+    }
+
+    for (yard, tracks) in &mut station.tracks {
+      if yard.is_empty() || !yard.chars().all(|c| c.is_ascii_uppercase()) {
+        return invalid(format!(
+          "yard `{yard}` in station {short} must be upper-case letters"
+        ));
+      }
+      if tracks.is_empty() {
+        return invalid(format!("yard {short} {yard} has no tracks"));
+      }
+      tracks.sort_unstable();
+      if let Some(track) = tracks.windows(2).find(|w| w[0] == w[1]) {
+        return invalid(format!(
+          "track {short} {yard}{} is listed twice",
+          track[0]
+        ));
+      }
+    }
+    if let Some(((yard, track), _)) =
+      station.capacity.iter().find(|(_, cars)| **cars == 0)
+    {
+      return invalid(format!("track {short} {yard}{track} holds no cars"));
+    }
+  }
+
+  if !is_grade(file.default_grade) {
+    return invalid(format!(
+      "default grade must be from 0 to 10%, not {}",
+      file.default_grade
+    ));
+  }
+
+  for Connection {
+    between: [a, b],
+    km,
+    grade,
+  } in &file.connection
+  {
+    if let Some(unknown) = [a, b].into_iter().find(|s| !seen.contains(*s)) {
+      return invalid(format!("connection to unknown station `{unknown}`"));
+    }
+    if a == b {
+      return invalid(format!("station {a} is connected to itself"));
+    }
+    if !(km.is_finite() && *km > 0.0) {
+      return invalid(format!(
+        "connection between {a} and {b} must be longer than 0 km"
+      ));
+    }
+    if let Some(grade) = grade
+      && !is_grade(*grade)
+    {
+      return invalid(format!(
+        "connection between {a} and {b} must have a grade from 0 to 10%, \
+         not {grade}"
+      ));
+    }
+  }
+
+  let mut map = Map {
+    stations,
+    connections: file.connection,
+    default_grade: file.default_grade,
+    distances: Vec::new(),
+    next: Vec::new(),
+  };
+  map.compute_distances();
+
+  // Without any connections the planner is simply unavailable, but a partly
+  // connected map is almost certainly a mistake.
+  if !map.connections.is_empty()
+    && let Some(station) = map
+      .stations
+      .iter()
+      .find(|s| map.distance(map.stations[0].short, s.short).is_none())
+  {
+    return invalid(format!(
+      "station {} can't be reached from {}",
+      station.short, map.stations[0].short
+    ));
+  }
+
+  Ok(map)
+}
+
+fn is_grade(grade: f32) -> bool {
+  (0.0..=10.0).contains(&grade)
+}
+
+fn is_hex_color(color: &str) -> bool {
+  color.strip_prefix('#').is_some_and(|hex| {
+    matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit())
+  })
+}
@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{DestinationKind, Event, Order};
+
+/// Where an order is in its life, worked out from its done checkboxes and
+/// whether it has been closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OrderStatus {
+  /// Nothing has been picked up yet.
+  Accepted,
+  /// Some, but not all, of the cars have been picked up.
+  PickedUp,
+  /// Every car has been picked up, but not all of them dropped off.
+  InTransit,
+  /// Every car has been dropped off.
+  Delivered,
+  /// The job was turned in after being delivered.
+  Completed,
+  /// The job was given up on.
+  Abandoned,
+}
+
+impl OrderStatus {
+  pub fn name(self) -> &'static str {
+    match self {
+      OrderStatus::Accepted => "Accepted",
+      OrderStatus::PickedUp => "Picked up",
+      OrderStatus::InTransit => "In transit",
+      OrderStatus::Delivered => "Delivered",
+      OrderStatus::Completed => "Completed",
+      OrderStatus::Abandoned => "Abandoned",
+    }
+  }
+
+  /// Whether there is still work to do on the order.
+  pub fn is_active(self) -> bool {
+    matches!(
+      self,
+      OrderStatus::Accepted | OrderStatus::PickedUp | OrderStatus::InTransit
+    )
+  }
+}
+
+impl std::fmt::Display for OrderStatus {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    // Also used as a CSS class, e.g. `in-transit`.
+    match self {
+      OrderStatus::Accepted => write!(f, "accepted"),
+      OrderStatus::PickedUp => write!(f, "picked-up"),
+      OrderStatus::InTransit => write!(f, "in-transit"),
+      OrderStatus::Delivered => write!(f, "delivered"),
+      OrderStatus::Completed => write!(f, "completed"),
+      OrderStatus::Abandoned => write!(f, "abandoned"),
+    }
+  }
+}
+
+/// How an order was closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Closed {
+  Completed,
+  Abandoned,
+}
+
+/// Why a change isn't allowed in the order's current status.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransitionError {
+  /// A dropoff was checked before every pickup was.
+  DropoffBeforePickup,
+  /// A pickup was unchecked, or added, after something was dropped off.
+  PickupAfterDropoff,
+  /// Completed and abandoned orders have to be reopened to change them.
+  Closed(OrderStatus),
+  /// Only delivered orders can be completed.
+  NotDelivered(OrderStatus),
+}
+
+impl std::fmt::Display for TransitionError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      TransitionError::DropoffBeforePickup => write!(
+        f,
+        "cars can't be dropped off before every pickup is checked off"
+      ),
+      TransitionError::PickupAfterDropoff => write!(
+        f,
+        "pickups can't be unchecked or added once cars are dropped off"
+      ),
+      TransitionError::Closed(status) => write!(
+        f,
+        "the order is {}; reopen it to change it",
+        status.name().to_lowercase()
+      ),
+      TransitionError::NotDelivered(status) => write!(
+        f,
+        "only delivered orders can be completed, but this one is {}",
+        status.name().to_lowercase()
+      ),
+    }
+  }
+}
+
+impl std::error::Error for TransitionError {}
+
+impl Order {
+  pub fn status(&self) -> OrderStatus {
+    match self.closed {
+      Some(Closed::Completed) => return OrderStatus::Completed,
+      Some(Closed::Abandoned) => return OrderStatus::Abandoned,
+      None => {}
+    }
+
+    let done = |side| self.legs(side).filter(|leg| leg.done).count();
+    let count = |side| self.legs(side).count();
+    let (from, to) = (DestinationKind::From, DestinationKind::To);
+    if done(from) == count(from) && done(to) == count(to) {
+      OrderStatus::Delivered
+    } else if done(from) == count(from) {
+      OrderStatus::InTransit
+    } else if done(from) > 0 {
+      OrderStatus::PickedUp
+    } else {
+      OrderStatus::Accepted
+    }
+  }
+
+  /// Checks that an order that arrives whole, rather than being built up by
+  /// events, is somewhere its lifecycle could have got it to: nothing dropped
+  /// off before every pickup, and only delivered orders completed.
+  pub fn check_lifecycle(&self) -> Result<(), TransitionError> {
+    let open = Order {
+      closed: None,
+      ..self.clone()
+    };
+    let status = open.status();
+    if self.legs(DestinationKind::To).any(|leg| leg.done)
+      && !self.legs(DestinationKind::From).all(|leg| leg.done)
+    {
+      return Err(TransitionError::DropoffBeforePickup);
+    }
+    if self.closed == Some(Closed::Completed)
+      && status != OrderStatus::Delivered
+    {
+      return Err(TransitionError::NotDelivered(status));
+    }
+    Ok(())
+  }
+
+  /// Whether a leg on `side` that is `done` or not can be toggled.
+  pub fn can_toggle(&self, side: DestinationKind, done: bool) -> bool {
+    let event = Event::SetDone {
+      guid: self.guid,
+      destination: side,
+      leg: 0,
+      done: !done,
+    };
+    self.check_transition(&event).is_ok()
+  }
+
+  /// Checks that `event` is a legal step from the order's current status.
+  /// Cars have to be picked up before they are dropped off, only delivered
+  /// orders can be completed, and closed orders have to be reopened before
+  /// they change or their bonus timer is started or stopped.
+  pub fn check_transition(&self, event: &Event) -> Result<(), TransitionError> {
+    let status = self.status();
+    let closed = self.closed.is_some();
+    let any_done = |side| self.legs(side).any(|leg| leg.done);
+    let all_done = |side| self.legs(side).all(|leg| leg.done);
+
+    match event {
+      Event::Edit { .. }
+      | Event::SetDone { .. }
+      | Event::AddLeg { .. }
+      | Event::EditLeg { .. }
+      | Event::RemoveLeg { .. }
+      | Event::Take { .. }
+        if closed =>
+      {
+        Err(TransitionError::Closed(status))
+      }
+      Event::SetDone {
+        destination: DestinationKind::To,
+        done: true,
+        ..
+      } if !all_done(DestinationKind::From) => {
+        Err(TransitionError::DropoffBeforePickup)
+      }
+      Event::SetDone {
+        destination: DestinationKind::From,
+        done: false,
+        ..
+      }
+      | Event::AddLeg {
+        destination: DestinationKind::From,
+        ..
+      } if any_done(DestinationKind::To) => {
+        Err(TransitionError::PickupAfterDropoff)
+      }
+      Event::Close {
+        closed: Some(_), ..
+      } if closed => Err(TransitionError::Closed(status)),
+      Event::Close {
+        closed: Some(Closed::Completed),
+        ..
+      } if status != OrderStatus::Delivered => {
+        Err(TransitionError::NotDelivered(status))
+      }
+      _ => Ok(()),
+    }
+  }
+}
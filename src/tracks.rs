@@ -0,0 +1,143 @@
+use maud::{Markup, html};
+
+use crate::{Destination, DestinationKind, Order, OrderStore, stations};
+
+/// The orders that involve one track.
+#[derive(Debug)]
+pub struct TrackUse<'a> {
+  /// The track. Its `done` flag means nothing.
+  pub track: Destination,
+  /// Orders whose cars are sitting on the track, waiting to be picked up.
+  pub parked: Vec<&'a Order>,
+  /// Orders whose cars are on their way to the track.
+  pub inbound: Vec<&'a Order>,
+}
+
+impl TrackUse<'_> {
+  /// Reasons the track may end up with more than it should.
+  pub fn conflicts(&self) -> Vec<String> {
+    let mut conflicts = Vec::new();
+
+    if self.inbound.len() > 1 {
+      conflicts.push(format!("{} orders are headed here", self.inbound.len()));
+    }
+    if !self.inbound.is_empty()
+      && self
+        .parked
+        .iter()
+        .any(|p| self.inbound.iter().all(|i| i.guid != p.guid))
+    {
+      conflicts.push("cars are headed here before it is cleared".to_string());
+    }
+    if let Some(capacity) = self.track.capacity() {
+      let cars = self
+        .inbound
+        .iter()
+        .flat_map(|o| {
+          o.legs(DestinationKind::To)
+            .filter(|to| !to.done && to.same_track(&self.track))
+            .map(|to| o.leg_cars(DestinationKind::To, to))
+        })
+        .fold(0, u16::saturating_add);
+      if cars > capacity {
+        conflicts.push(format!(
+          "{cars} cars are headed here, but it holds {capacity}"
+        ));
+      }
+    }
+
+    conflicts
+  }
+}
+
+impl OrderStore {
+  /// Every track that has unfinished orders parked on it or headed to it, in
+  /// map order.
+  pub fn occupancy(&self) -> Vec<TrackUse<'_>> {
+    let mut tracks: Vec<TrackUse> = Vec::new();
+    let mut track_use = |dest: &Destination| -> usize {
+      match tracks.iter().position(|t| t.track.same_track(dest)) {
+        Some(i) => i,
+        None => {
+          tracks.push(TrackUse {
+            track: Destination {
+              done: false,
+              cars: 0,
+              ..dest.clone()
+            },
+            parked: Vec::new(),
+            inbound: Vec::new(),
+          });
+          tracks.len() - 1
+        }
+      }
+    };
+
+    let mut parked = Vec::new();
+    let mut inbound = Vec::new();
+    for order in self.orders.iter().filter(|o| o.status().is_active()) {
+      for leg in order.legs(DestinationKind::From).filter(|leg| !leg.done) {
+        parked.push((track_use(leg), order));
+      }
+      for leg in order.legs(DestinationKind::To).filter(|leg| !leg.done) {
+        inbound.push((track_use(leg), order));
+      }
+    }
+    // An order with two legs on the same track is still only listed once.
+    for (i, order) in parked {
+      if !tracks[i].parked.iter().any(|o| o.guid == order.guid) {
+        tracks[i].parked.push(order);
+      }
+    }
+    for (i, order) in inbound {
+      if !tracks[i].inbound.iter().any(|o| o.guid == order.guid) {
+        tracks[i].inbound.push(order);
+      }
+    }
+
+    let station_index = |dest: &Destination| {
+      stations()
+        .iter()
+        .position(|s| s.short == dest.station)
+        .unwrap_or(usize::MAX)
+    };
+    tracks.sort_by(|a, b| {
+      (station_index(&a.track), a.track.yard, a.track.track).cmp(&(
+        station_index(&b.track),
+        b.track.yard,
+        b.track.track,
+      ))
+    });
+
+    tracks
+  }
+
+  pub fn render_occupancy(&self) -> Markup {
+    let render_orders = |orders: &[&Order]| {
+      html!(
+        @for order in orders {
+          div { (order) }
+        }
+      )
+    };
+
+    html!(
+      @for track_use in self.occupancy() {
+        @let conflicts = track_use.conflicts();
+        tr.conflict[!conflicts.is_empty()] {
+          td.dest {
+            div.bg.(track_use.track.station) {}
+            div.content { (track_use.track) }
+          }
+          td { (render_orders(&track_use.parked)) }
+          td { (render_orders(&track_use.inbound)) }
+          td {
+            @for conflict in conflicts {
+              div.warning { (conflict) }
+            }
+          }
+        }
+      }
+    )
+  }
+}
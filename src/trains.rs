@@ -0,0 +1,279 @@
+use internment::Intern;
+use maud::{Markup, html};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  DestinationKind, Order, OrderStatus, OrderStore, loco, locos, map,
+};
+
+/// A consist: the orders one locomotive is moving.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Train {
+  pub id: usize,
+  pub name: String,
+  /// The code of the loco pulling the train.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub loco: Option<Intern<String>>,
+  /// The most cars the train can be, say to fit the sidings on its route. 0
+  /// means no limit. How much it can haul comes from its loco.
+  pub max_cars: u16,
+}
+
+impl Train {
+  /// Warnings about the loaded `orders` being more than the train can take.
+  pub fn warnings(&self, orders: &[&Order]) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    let cars = loaded(orders).cars;
+    if self.max_cars > 0 && cars > u64::from(self.max_cars) {
+      warnings.push(format!(
+        "{cars} cars loaded is more than the {} it can take",
+        self.max_cars
+      ));
+    }
+    warnings.extend(loco_warning(self.loco, orders));
+
+    warnings
+  }
+}
+
+impl Order {
+  /// Whether some of the order's cars have been picked up but not all of them
+  /// dropped off yet, so they are on the train.
+  pub fn is_loaded(&self) -> bool {
+    matches!(
+      self.status(),
+      OrderStatus::PickedUp | OrderStatus::InTransit
+    )
+  }
+
+  /// The steepest grade in percent between the order's pickup and the
+  /// dropoffs it has left. Stations the map can't route between are assumed
+  /// to be as steep as its default grade.
+  pub fn grade(&self) -> f32 {
+    let map = map();
+    self
+      .legs(DestinationKind::To)
+      .filter(|to| !to.done)
+      .map(|to| {
+        map
+          .grade(self.from.station, to.station)
+          .unwrap_or(map.default_grade)
+      })
+      .fold(0.0, f32::max)
+  }
+}
+
+/// The tonnes and cars of the active `orders`, which are the work the train
+/// has left, whether it's been picked up yet or not.
+fn consist(orders: &[&Order]) -> (u64, u64) {
+  orders.iter().filter(|o| o.status().is_active()).fold(
+    (0, 0),
+    |(tonnes, cars), o| {
+      (tonnes + u64::from(o.tonnes), cars + u64::from(o.cars))
+    },
+  )
+}
+
+/// What is on a train: its [loaded](Order::is_loaded) orders.
+#[derive(Default)]
+struct Load {
+  tonnes: u64,
+  cars: u64,
+  /// The steepest grade the orders still have to climb.
+  grade: f32,
+}
+
+fn loaded(orders: &[&Order]) -> Load {
+  orders
+    .iter()
+    .filter(|o| o.is_loaded())
+    .fold(Load::default(), |load, o| Load {
+      tonnes: load.tonnes + u64::from(o.tonnes),
+      cars: load.cars + u64::from(o.cars),
+      grade: load.grade.max(o.grade()),
+    })
+}
+
+/// A warning when the loaded `orders` are more than `loco` can haul up the
+/// steepest grade any of them still has to climb.
+fn loco_warning(
+  loco: Option<Intern<String>>,
+  orders: &[&Order],
+) -> Option<String> {
+  let loco = crate::loco(loco?)?;
+  let Load { tonnes, grade, .. } = loaded(orders);
+  let limit = loco.max_tonnes(grade);
+
+  (tonnes > u64::from(limit)).then(|| {
+    format!(
+      "{tonnes}t loaded is more than a {} can haul up a {grade:.1}% grade, \
+       about {limit}t",
+      loco.code
+    )
+  })
+}
+
+impl OrderStore {
+  pub fn train(&self, id: usize) -> Option<&Train> {
+    self.trains.iter().find(|t| t.id == id)
+  }
+
+  pub fn train_mut(&mut self, id: usize) -> Option<&mut Train> {
+    self.trains.iter_mut().find(|t| t.id == id)
+  }
+
+  pub fn add_train(&mut self, name: &str) {
+    self.trains.push(Train {
+      id: self.train_idx,
+      name: name.to_string(),
+      ..Default::default()
+    });
+    self.train_idx += 1;
+  }
+
+  /// Takes `order` off its train if there's no such train on the board, as
+  /// happens to an order restored after its train was removed.
+  pub(crate) fn clear_missing_train(&self, order: &mut Order) {
+    if order.train.is_some_and(|id| self.train(id).is_none()) {
+      order.train = None;
+    }
+  }
+
+  /// Removes a train. Its orders are left without one.
+  pub fn remove_train(&mut self, id: usize) {
+    self.trains.retain(|t| t.id != id);
+    for order in &mut self.orders {
+      if order.train == Some(id) {
+        order.train = None;
+      }
+    }
+  }
+
+  /// Sets the loco of a train, or of the orders without one.
+  pub fn set_loco(
+    &mut self,
+    train: Option<usize>,
+    loco: Option<Intern<String>>,
+  ) {
+    match train {
+      Some(id) => {
+        if let Some(train) = self.train_mut(id) {
+          train.loco = loco;
+        }
+      }
+      None => self.loco = loco,
+    }
+  }
+
+  /// The orders assigned to a train, or to none.
+  pub fn train_orders(&self, train: Option<usize>) -> Vec<&Order> {
+    self.orders.iter().filter(|o| o.train == train).collect()
+  }
+
+  /// A totals row for each train, and one for orders without a train, to go
+  /// under the orders. Nothing when there are no trains or locos.
+  pub fn render_train_totals(&self) -> Markup {
+    let render_row = |name: Markup, orders: &[&Order], warnings: &[String]| {
+      let (tonnes, cars) = consist(orders);
+      html!(
+        tr.train-totals {
+          td { (name) }
+          td colspan="3" { (orders.len()) }
+          td { (tonnes) "t" }
+          td { (cars) " cars" }
+          td colspan="4" {
+            @for warning in warnings {
+              div.warning { (warning) }
+            }
+          }
+        }
+      )
+    };
+
+    let unassigned = self.train_orders(None);
+    html!(
+      @for train in &self.trains {
+        @let orders = self.train_orders(Some(train.id));
+        (render_row(html!(b { (train.name) }), &orders, &train.warnings(&orders)))
+      }
+      @if (!self.trains.is_empty() || self.loco.is_some()) && !unassigned.is_empty() {
+        @let warnings = Vec::from_iter(loco_warning(self.loco, &unassigned));
+        (render_row(html!(i { "No train" }), &unassigned, &warnings))
+      }
+    )
+  }
+
+  /// The trains page: each train's loco, limits and orders, with their
+  /// totals.
+  pub fn render_trains(&self) -> Markup {
+    let render_orders = |orders: &[&Order]| {
+      html!(
+        @if orders.is_empty() {
+          p { "No orders." }
+        } @else {
+          ul {
+            @for order in orders {
+              li.completed[!order.status().is_active()] { (order) }
+            }
+          }
+        }
+      )
+    };
+    let render_loco = |train: Option<usize>,
+                       current: Option<Intern<String>>,
+                       orders: &[&Order]| {
+      let Load { tonnes, grade, .. } = loaded(orders);
+      let train = train.map(|t| t.to_string()).unwrap_or_default();
+      html!(
+        p {
+          "Loco: "
+          select name="loco" hx-post="api/loco" hx-target="#trains" hx-vals={"{\"train\": \"" (train) "\"}"} {
+            option value="" selected[current.is_none()] { "-" }
+            @for l in locos() {
+              option value=(l.code) selected[current == Some(l.code)] { (l.name) }
+            }
+          }
+          @if let Some(l) = current.and_then(loco) {
+            " " (tonnes) "t loaded, of about " (l.max_tonnes(grade))
+            "t it can haul up a " (format!("{grade:.1}")) "% grade"
+          }
+        }
+      )
+    };
+
+    let unassigned = self.train_orders(None);
+    html!(
+      @for train in &self.trains {
+        @let url = format!("api/trains/{}", train.id);
+        @let orders = self.train_orders(Some(train.id));
+        @let load = loaded(&orders);
+        section.train {
+          h3 {
+            input name="name" hx-post=(url) hx-target="#trains" value=(train.name);
+            button hx-delete=(url) hx-target="#trains" hx-confirm={"Remove " (train.name) "? Its orders are kept."} { "x" }
+          }
+          (render_loco(Some(train.id), train.loco, &orders))
+          p {
+            "Hauling " (load.tonnes) "t, " (load.cars) " of "
+            input name="max-cars" type="number" hx-post=(url) hx-target="#trains" value=(train.max_cars) min="0" title="0 means no limit";
+            " cars"
+          }
+          @for warning in train.warnings(&orders) {
+            div.warning { (warning) }
+          }
+          (render_orders(&orders))
+        }
+      }
+      section.train {
+        h3 { "No train" }
+        (render_loco(None, self.loco, &unassigned))
+        @if let Some(warning) = loco_warning(self.loco, &unassigned) {
+          div.warning { (warning) }
+        }
+        (render_orders(&unassigned))
+      }
+    )
+  }
+}
@@ -0,0 +1,194 @@
+use internment::Intern;
+use maud::{Markup, html};
+use serde::{Deserialize, Deserializer, de::IntoDeserializer};
+
+use crate::{
+  Destination, DestinationKind, Order, OrderKind, OrderStatus, stations,
+};
+
+/// How far along an order is, as far as filtering goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StatusFilter {
+  /// Nothing picked up yet.
+  Pending,
+  /// Cars are on the train.
+  Loaded,
+  /// Delivered, completed or abandoned.
+  Done,
+}
+
+impl StatusFilter {
+  const ALL: [(StatusFilter, &str); 3] = [
+    (StatusFilter::Pending, "pending"),
+    (StatusFilter::Loaded, "loaded"),
+    (StatusFilter::Done, "done"),
+  ];
+
+  fn matches(self, status: OrderStatus) -> bool {
+    match self {
+      StatusFilter::Pending => status == OrderStatus::Accepted,
+      StatusFilter::Loaded => {
+        matches!(status, OrderStatus::PickedUp | OrderStatus::InTransit)
+      }
+      StatusFilter::Done => !status.is_active(),
+    }
+  }
+}
+
+/// What the orders can be sorted by. Ties keep the board's order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortKey {
+  Id,
+  From,
+  To,
+  Tonnes,
+  Cars,
+  /// When the bonus runs out, soonest first. Orders without a running timer
+  /// come last.
+  TimeLeft,
+}
+
+impl SortKey {
+  const ALL: [(SortKey, &str, &str); 6] = [
+    (SortKey::Id, "id", "ID"),
+    (SortKey::From, "from", "From station"),
+    (SortKey::To, "to", "To station"),
+    (SortKey::Tonnes, "tonnes", "Tonnes"),
+    (SortKey::Cars, "cars", "Cars"),
+    (SortKey::TimeLeft, "time-left", "Time left"),
+  ];
+}
+
+/// How the orders can be grouped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GroupBy {
+  /// Under each station the orders have work at. See
+  /// [`OrderStore::render_by_station`](crate::OrderStore::render_by_station).
+  Station,
+}
+
+/// Which orders a player wants to see, and in what order. It comes from the
+/// query string, e.g. `?kind=FH&station=SM&sort=tonnes`, so every player can
+/// keep their own. Blank parameters are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct OrderView {
+  #[serde(deserialize_with = "blank_as_none")]
+  pub kind: Option<OrderKind>,
+  /// A station any of the order's tracks is at.
+  #[serde(deserialize_with = "blank_as_none")]
+  pub station: Option<Intern<String>>,
+  /// A yard any of the order's tracks is in, at `station` if that is set.
+  #[serde(deserialize_with = "blank_as_none")]
+  pub yard: Option<Intern<String>>,
+  #[serde(deserialize_with = "blank_as_none")]
+  pub status: Option<StatusFilter>,
+  /// Text the notes have to contain, ignoring case.
+  #[serde(deserialize_with = "blank_as_none")]
+  pub notes: Option<String>,
+  #[serde(deserialize_with = "blank_as_none")]
+  pub sort: Option<SortKey>,
+  #[serde(deserialize_with = "blank_as_none")]
+  pub group: Option<GroupBy>,
+}
+
+/// Reads a blank form field as `None`, since clearing an input still sends it.
+fn blank_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+  D: Deserializer<'de>,
+  T: Deserialize<'de>,
+{
+  match Option::<String>::deserialize(deserializer)? {
+    Some(value) if !value.trim().is_empty() => {
+      T::deserialize(value.trim().into_deserializer()).map(Some)
+    }
+    _ => Ok(None),
+  }
+}
+
+impl OrderView {
+  /// Whether every order is shown in the board's order.
+  pub fn is_empty(&self) -> bool {
+    *self == Self::default()
+  }
+
+  pub fn matches(&self, order: &Order) -> bool {
+    let at = |leg: &Destination| {
+      self.station.is_none_or(|s| leg.station == s)
+        && self.yard.is_none_or(|y| leg.yard.eq_ignore_ascii_case(&y))
+    };
+    let notes = self.notes.as_ref().map(|n| n.to_lowercase());
+
+    self.kind.is_none_or(|k| order.kind == k)
+      && (self.station.is_none() && self.yard.is_none()
+        || order
+          .legs(DestinationKind::From)
+          .chain(order.legs(DestinationKind::To))
+          .any(at))
+      && self.status.is_none_or(|s| s.matches(order.status()))
+      && notes.is_none_or(|n| order.notes.to_lowercase().contains(&n))
+  }
+
+  /// The orders to show, sorted.
+  pub fn apply<'a>(&self, orders: &'a [Order]) -> Vec<&'a Order> {
+    let mut shown: Vec<_> = orders.iter().filter(|o| self.matches(o)).collect();
+    match self.sort {
+      Some(SortKey::Id) => shown.sort_by_key(|o| o.id),
+      Some(SortKey::From) => shown.sort_by_key(|o| o.from.station.as_str()),
+      Some(SortKey::To) => shown.sort_by_key(|o| o.to.station.as_str()),
+      Some(SortKey::Tonnes) => shown.sort_by_key(|o| o.tonnes),
+      Some(SortKey::Cars) => shown.sort_by_key(|o| o.cars),
+      Some(SortKey::TimeLeft) => shown.sort_by_key(|o| {
+        match o.deadline().filter(|_| o.status().is_active()) {
+          Some(deadline) => (false, deadline),
+          None => (true, 0),
+        }
+      }),
+      None => {}
+    }
+    shown
+  }
+
+  /// The filter bar above the orders, filled in with this view.
+  pub fn render_form(&self) -> Markup {
+    html!(
+      form #filter hx-get="api/orders" hx-target="#orders" hx-trigger="change, input delay:300ms from:input[name=notes]" {
+        select name="kind" title="Kind" {
+          option value="" { "Any kind" }
+          @for k in OrderKind::ALL {
+            option value=(k) selected[self.kind == Some(k)] { (k.name()) }
+          }
+        }
+        select name="station" title="Station" {
+          option value="" { "Any station" }
+          @for s in stations() {
+            option value=(s.short) selected[self.station == Some(s.short)] { (s.long) }
+          }
+        }
+        input name="yard" placeholder="Yard" size="4" value=[self.yard.map(|y| y.to_string())];
+        select name="status" title="Status" {
+          option value="" { "Any status" }
+          @for (status, value) in StatusFilter::ALL {
+            option value=(value) selected[self.status == Some(status)] { (value) }
+          }
+        }
+        input name="notes" placeholder="Notes contain" value=[self.notes.as_deref()];
+        select name="sort" title="Sort by" {
+          option value="" { "Board order" }
+          @for (key, value, name) in SortKey::ALL {
+            option value=(value) selected[self.sort == Some(key)] { "Sort by " (name) }
+          }
+        }
+        select name="group" title="Group by" {
+          option value="" { "No grouping" }
+          option value="station" selected[self.group == Some(GroupBy::Station)] { "Group by station" }
+        }
+        " "
+        a href="." { "Clear" }
+      }
+    )
+  }
+}
@@ -0,0 +1,74 @@
+use dv_tracker::{DestinationKind, Entry, Event, Order, OrderStore};
+
+/// Applies `events` in turn, as if read back from the journal.
+fn apply(store: &mut OrderStore, events: impl IntoIterator<Item = Event>) {
+  for event in events {
+    let entry = Entry {
+      seq: store.seq() + 1,
+      at: 1000 + store.seq(),
+      who: "test".to_string(),
+      event,
+    };
+    store.apply(&entry);
+  }
+}
+
+fn deliver(guid: usize) -> [Event; 2] {
+  [DestinationKind::From, DestinationKind::To].map(|destination| {
+    Event::SetDone {
+      guid,
+      destination,
+      leg: 0,
+      done: true,
+    }
+  })
+}
+
+/// The guids on the board, and the guid and batch of everything archived.
+fn state(store: &OrderStore) -> (Vec<usize>, Vec<(usize, usize)>) {
+  (
+    store.orders.iter().map(|o| o.guid).collect(),
+    store
+      .history
+      .iter()
+      .map(|a| (a.order.guid, a.batch))
+      .collect(),
+  )
+}
+
+#[test]
+fn undo_and_redo_archiving_and_restoring() {
+  let mut store = OrderStore::new();
+  let order = Order::parse("FH01 SM A3:4 FM B5:4 400 4").unwrap();
+  apply(
+    &mut store,
+    (0..3).map(|_| Event::Add {
+      order: order.clone(),
+    }),
+  );
+  apply(&mut store, deliver(0).into_iter().chain(deliver(1)));
+  apply(
+    &mut store,
+    [Event::Archive { guid: 0 }, Event::ArchiveFinished],
+  );
+
+  let archived = state(&store);
+  assert_eq!(archived, (vec![2], vec![(0, 0), (1, 1)]));
+
+  apply(&mut store, [Event::Restore { batch: 0 }]);
+  let restored = state(&store);
+  assert_eq!(restored, (vec![2, 0], vec![(1, 1)]));
+
+  apply(&mut store, [Event::Undo]);
+  assert_eq!(state(&store), archived);
+  apply(&mut store, [Event::Undo]);
+  assert_eq!(state(&store), (vec![1, 2], vec![(0, 0)]));
+  apply(&mut store, [Event::Undo]);
+  assert_eq!(state(&store), (vec![0, 1, 2], vec![]));
+
+  apply(&mut store, [Event::Redo, Event::Redo]);
+  assert_eq!(state(&store), archived);
+  apply(&mut store, [Event::Redo]);
+  assert_eq!(state(&store), restored);
+  assert!(!store.can_redo());
+}
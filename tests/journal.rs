@@ -0,0 +1,52 @@
+use std::{fs, path::PathBuf};
+
+use dv_tracker::{Entry, Event, Journal};
+
+/// A journal path of its own for each test, with nothing there yet.
+fn scratch(name: &str) -> PathBuf {
+  let path = std::env::temp_dir()
+    .join(format!("dv-tracker-{}", std::process::id()))
+    .join(format!("{name}.jsonl"));
+  let _ = fs::remove_file(&path);
+  path
+}
+
+/// Writes `count` entries to a fresh journal and returns its lines.
+fn write(path: &PathBuf, count: u64) -> Vec<String> {
+  let (mut journal, _) = Journal::open(path).unwrap();
+  for seq in 1..=count {
+    journal
+      .append(&Entry::new(seq, "test", Event::Archive { guid: 0 }))
+      .unwrap();
+  }
+  fs::read_to_string(path)
+    .unwrap()
+    .lines()
+    .map(String::from)
+    .collect()
+}
+
+#[test]
+fn torn_tail_is_truncated() {
+  let path = scratch("torn_tail");
+  let lines = write(&path, 3);
+  let torn = format!("{}\n{}\n{{\"seq\":3,", lines[0], lines[1]);
+  fs::write(&path, &torn).unwrap();
+
+  let (_, entries) = Journal::open(&path).unwrap();
+  assert_eq!(entries.len(), 2);
+  let kept = fs::read_to_string(&path).unwrap();
+  assert_eq!(kept, format!("{}\n{}\n", lines[0], lines[1]));
+}
+
+#[test]
+fn bad_line_before_the_end_is_an_error() {
+  let path = scratch("bad_middle");
+  let mut lines = write(&path, 5);
+  lines[1] = "not json".to_string();
+  let corrupt = lines.join("\n") + "\n";
+  fs::write(&path, &corrupt).unwrap();
+
+  assert!(Journal::open(&path).is_err());
+  assert_eq!(fs::read_to_string(&path).unwrap(), corrupt);
+}
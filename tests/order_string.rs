@@ -0,0 +1,167 @@
+use dv_tracker::{
+  Destination, DestinationKind, Event, LegEdit, Order, OrderEdit, OrderKind,
+  stations,
+};
+use internment::Intern;
+use proptest::{
+  prelude::*,
+  sample::{Index, select},
+};
+
+/// Every destination on the map.
+fn destinations() -> Vec<Destination> {
+  stations()
+    .iter()
+    .flat_map(|station| {
+      station.tracks.iter().flat_map(|(yard, tracks)| {
+        tracks.iter().map(|track| Destination {
+          station: station.short,
+          yard: *yard,
+          track: *track,
+          done: false,
+          cars: 0,
+        })
+      })
+    })
+    .collect()
+}
+
+/// A leg's destination, with some cars on it when it's one of several.
+fn leg(
+  destinations: &[Destination],
+  (index, cars): &(Index, u16),
+) -> Destination {
+  Destination {
+    cars: *cars,
+    ..index.get(destinations).clone()
+  }
+}
+
+fn order() -> impl Strategy<Value = Order> {
+  (
+    select(OrderKind::ALL.to_vec()),
+    any::<u8>(),
+    select(destinations()),
+    any::<Index>(),
+    any::<String>(),
+    any::<u16>(),
+    any::<u16>(),
+    prop::collection::vec(any::<(Index, u16)>(), 0..3),
+    prop::collection::vec(any::<(Index, u16)>(), 0..3),
+  )
+    .prop_map(
+      |(kind, id, from, to, notes, tonnes, cars, more_from, more_to)| {
+        // Shunting orders have to stay at the station they start from.
+        let allowed = destinations()
+          .into_iter()
+          .filter(|to| !kind.stays_in_station() || to.station == from.station)
+          .collect::<Vec<_>>();
+        let more_from: Vec<_> =
+          more_from.iter().map(|l| leg(&allowed, l)).collect();
+        let more_to: Vec<_> =
+          more_to.iter().map(|l| leg(&allowed, l)).collect();
+        Order {
+          kind,
+          id,
+          from: Destination {
+            cars: cars / 2,
+            ..from
+          },
+          to: leg(&allowed, &(to, cars / 3)),
+          notes,
+          tonnes,
+          cars,
+          more_from,
+          more_to,
+          ..Default::default()
+        }
+      },
+    )
+}
+
+proptest! {
+  #[test]
+  fn parse_reads_back_display(order in order()) {
+    prop_assert_eq!(Order::parse(&order.to_string()), Ok(order));
+  }
+
+  #[test]
+  fn display_is_one_line(order in order()) {
+    prop_assert!(!order.to_string().contains('\n'));
+  }
+}
+
+#[test]
+fn display_leaves_off_empty_fields() {
+  let order = Order::parse("fh1 sm b1 sw b1").unwrap();
+  assert_eq!(order.to_string(), "FH01 SM B1 SW B1");
+
+  let order = Order::parse("LH02 SM B1 SW B1 0 6").unwrap();
+  assert_eq!(order.to_string(), "LH02 SM B1 SW B1 0 6");
+
+  let order = Order::parse(r#"SL03 SM B1 SM A3 600 "say \"hi\"\n""#).unwrap();
+  assert_eq!(order.notes, "say \"hi\"\n");
+  assert_eq!(order.to_string(), r#"SL03 SM B1 SM A3 600 "say \"hi\"\n""#);
+}
+
+#[test]
+fn legs_round_trip() {
+  let input = "SL02 FM A1:3 + FM A2:2 FM B5 300 5";
+  let order = Order::parse(input).unwrap();
+  assert_eq!(order.more_from.len(), 1);
+  assert_eq!(order.from.cars, 3);
+  assert_eq!(order.to_string(), input);
+}
+
+#[test]
+fn bad_notes_are_errors() {
+  for input in [
+    r#"FH01 SM B1 SW B1 "unterminated"#,
+    r#"FH01 SM B1 SW B1 "notes" 600"#,
+    r#"FH01 SM B1 SW B1 "bad \q escape""#,
+    "SU01 SM B1 SW B1",
+  ] {
+    assert!(Order::parse(input).is_err(), "{input}");
+  }
+}
+
+#[test]
+fn error_columns_count_characters_of_the_input() {
+  // The missing id is right after the six characters of `straße`, even though
+  // it uppercases to seven.
+  let error = Order::parse("straße FH01").unwrap_err();
+  assert_eq!(error.span.start, 6);
+  assert!(error.to_string().ends_with("(column 7)"), "{error}");
+}
+
+#[test]
+fn shunting_orders_move_every_leg() {
+  let sm = Intern::from_ref("SM");
+  for event in [
+    Event::Edit {
+      guid: 0,
+      edit: OrderEdit {
+        from_station: Some(sm),
+        ..Default::default()
+      },
+    },
+    Event::EditLeg {
+      guid: 0,
+      destination: DestinationKind::From,
+      leg: 1,
+      edit: LegEdit {
+        station: Some(sm),
+        ..Default::default()
+      },
+    },
+  ] {
+    let mut order = Order::parse("SL05 FM A1:3 + FM A2:2 FM B5").unwrap();
+    assert_eq!(order.check(&event), Ok(()), "{event:?}");
+    order.apply(&event);
+    assert_eq!(
+      order.to_string(),
+      "SL05 SM A3:3 + SM A3:2 SM A3",
+      "{event:?}"
+    );
+  }
+}
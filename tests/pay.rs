@@ -0,0 +1,32 @@
+use dv_tracker::{Order, OrderStore};
+
+#[test]
+fn deadline_past_the_end_of_time_is_no_deadline() {
+  let order = Order {
+    taken_at: Some(u64::MAX),
+    bonus_minutes: 10,
+    payout: 1000,
+    bonus: 500,
+    ..Order::parse("FH01 SM A3:4 FM B5:4 400 4").unwrap()
+  };
+  assert_eq!(order.deadline(), None);
+  assert_eq!(order.time_left(1000), None);
+  assert!(!order.bonus_lost(1000));
+  assert_eq!(order.earnings(1000), 1500);
+
+  let mut store = OrderStore::new();
+  store.orders.push(order);
+  assert_eq!(store.bonus_at_risk(1000), 0);
+  store.render();
+}
+
+#[test]
+fn time_left_saturates() {
+  let order = Order {
+    taken_at: Some(i64::MAX as u64 - 60),
+    bonus_minutes: 1,
+    ..Order::parse("FH01 SM A3:4 FM B5:4 400 4").unwrap()
+  };
+  assert_eq!(order.time_left(0), Some(i64::MAX));
+  assert_eq!(order.time_left(u64::MAX), Some(0));
+}
@@ -0,0 +1,73 @@
+use dv_tracker::{Action, Closed, Order, PlanError, map, plan};
+use internment::Intern;
+
+fn orders(orders: &[&str]) -> Vec<Order> {
+  orders
+    .iter()
+    .enumerate()
+    .map(|(guid, order)| Order {
+      guid,
+      ..Order::parse(order).unwrap()
+    })
+    .collect()
+}
+
+#[test]
+fn pickups_come_before_dropoffs() {
+  let orders = orders(&[
+    "FH01 SM A3:4 FM B5:4 400 4",
+    "FH02 FM A1:2 + FM A2:2 SM A4:4 300 4",
+    "FH03 CP A1:3 FM B6:3 250 3",
+    "LH04 HB B1:5 CP A2:5 500 5",
+  ]);
+  let plan = plan(map(), &orders, Some(Intern::from_ref("FM"))).unwrap();
+
+  assert_eq!(plan.stops.len(), 9);
+  for order in &orders {
+    let at = |action| {
+      plan
+        .stops
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.guid == order.guid && s.action == action)
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>()
+    };
+    let (pickups, dropoffs) = (at(Action::Pickup), at(Action::Dropoff));
+    assert!(
+      pickups.iter().max() < dropoffs.iter().min(),
+      "{order}: pickups at {pickups:?}, dropoffs at {dropoffs:?}"
+    );
+  }
+}
+
+#[test]
+fn nothing_to_plan() {
+  let mut orders = orders(&["FH01 SM A3:4 FM B5:4 400 4"]);
+  orders[0].closed = Some(Closed::Abandoned);
+  let start = Intern::from_ref("FM");
+
+  for orders in [&[][..], &orders] {
+    let (anywhere, from_start) =
+      (plan(map(), orders, None), plan(map(), orders, Some(start)));
+    for (plan, start) in [
+      (anywhere.unwrap(), map().stations[0].short),
+      (from_start.unwrap(), start),
+    ] {
+      assert!(plan.stops.is_empty());
+      assert_eq!(plan.km, 0.0);
+      assert_eq!(plan.start, start);
+    }
+  }
+}
+
+#[test]
+fn unknown_start() {
+  let orders = orders(&["FH01 SM A3:4 FM B5:4 400 4"]);
+  let start = Intern::from_ref("ZZ");
+
+  assert_eq!(
+    plan(map(), &orders, Some(start)).unwrap_err(),
+    PlanError::UnknownStart(start)
+  );
+}
@@ -0,0 +1,78 @@
+use std::sync::Once;
+
+use dv_tracker::{Closed, Order, OrderStore, parse_map, set_map};
+use internment::Intern;
+
+/// Two stations, with capacities on some of their tracks.
+const MAP: &str = r##"
+[[station]]
+short = "AA"
+long = "Station A"
+color = "#686868"
+[station.tracks]
+A = [{ track = 1, cars = 4 }, 2]
+
+[[station]]
+short = "BB"
+long = "Station B"
+color = "#4d544d"
+[station.tracks]
+B = [{ track = 1, cars = 6 }, 2]
+"##;
+
+fn load_map() {
+  static LOADED: Once = Once::new();
+  LOADED.call_once(|| set_map(parse_map(MAP).unwrap()).unwrap());
+}
+
+fn store(orders: &[&str]) -> OrderStore {
+  load_map();
+  let mut store = OrderStore::default();
+  for order in orders {
+    store.add(Order::parse(order).unwrap());
+  }
+  store
+}
+
+#[test]
+fn capacities_are_read_per_track() {
+  let map = parse_map(MAP).unwrap();
+  let a = &map.stations[0];
+  assert_eq!(a.capacity(Intern::from_ref("A"), 1), Some(4));
+  assert_eq!(a.capacity(Intern::from_ref("A"), 2), None);
+  assert_eq!(a.tracks[&Intern::from_ref("A")], [1, 2]);
+}
+
+#[test]
+fn built_in_map_has_capacities() {
+  let map = parse_map(include_str!("../stations.toml")).unwrap();
+  assert!(map.stations.iter().any(|s| !s.capacity.is_empty()));
+}
+
+#[test]
+fn an_order_too_long_for_its_track_warns() {
+  let store = store(&["FH01 AA A2:7 BB B1:7 300 7"]);
+  assert_eq!(
+    store.warnings(&store.orders[0]),
+    ["7 cars won't fit on BB B1, which holds 6"]
+  );
+}
+
+#[test]
+fn orders_headed_to_the_same_track_add_up() {
+  let mut store = store(&[
+    "FH01 AA A2:4 BB B1:4 200 4",
+    "FH02 AA A2:3 BB B1:3 150 3",
+    "FH03 AA A2:3 BB B2:3 150 3",
+  ]);
+  let warning = ["orders headed to BB B1 add up to 7 cars, but it holds 6"];
+  assert_eq!(store.warnings(&store.orders[0]), warning);
+  assert_eq!(store.warnings(&store.orders[1]), warning);
+  // BB B2 has no known capacity.
+  assert!(store.warnings(&store.orders[2]).is_empty());
+
+  // Abandoned orders aren't headed anywhere any more.
+  store.orders[1].closed = Some(Closed::Abandoned);
+  assert!(store.warnings(&store.orders[0]).is_empty());
+  assert!(store.warnings(&store.orders[1]).is_empty());
+}
@@ -0,0 +1,210 @@
+use dv_tracker::{
+  Closed, DestinationKind, Event, LegEdit, Order, OrderEdit, OrderStatus,
+  TransitionError,
+};
+
+const FROM: DestinationKind = DestinationKind::From;
+const TO: DestinationKind = DestinationKind::To;
+
+/// An order with two pickups and two dropoffs.
+fn order() -> Order {
+  Order::parse("SL01 FM A1:3 + FM A2:2 FM B5:4 + FM B6:1 300 5").unwrap()
+}
+
+fn set_done(destination: DestinationKind, leg: usize, done: bool) -> Event {
+  Event::SetDone {
+    guid: 0,
+    destination,
+    leg,
+    done,
+  }
+}
+
+/// Applies `events` in turn, checking each one first.
+fn step(order: &mut Order, events: &[Event]) -> Result<(), TransitionError> {
+  for event in events {
+    order.check_transition(event)?;
+    order.apply(event);
+  }
+  Ok(())
+}
+
+fn delivered() -> Order {
+  let mut order = order();
+  step(
+    &mut order,
+    &[
+      set_done(FROM, 0, true),
+      set_done(FROM, 1, true),
+      set_done(TO, 0, true),
+      set_done(TO, 1, true),
+    ],
+  )
+  .unwrap();
+  order
+}
+
+#[test]
+fn status_follows_every_leg() {
+  let mut order = order();
+  assert_eq!(order.status(), OrderStatus::Accepted);
+
+  step(&mut order, &[set_done(FROM, 1, true)]).unwrap();
+  assert_eq!(order.status(), OrderStatus::PickedUp);
+  step(&mut order, &[set_done(FROM, 0, true)]).unwrap();
+  assert_eq!(order.status(), OrderStatus::InTransit);
+  step(&mut order, &[set_done(TO, 0, true)]).unwrap();
+  assert_eq!(order.status(), OrderStatus::InTransit);
+  step(&mut order, &[set_done(TO, 1, true)]).unwrap();
+  assert_eq!(order.status(), OrderStatus::Delivered);
+
+  order.closed = Some(Closed::Completed);
+  assert_eq!(order.status(), OrderStatus::Completed);
+  order.closed = Some(Closed::Abandoned);
+  assert_eq!(order.status(), OrderStatus::Abandoned);
+}
+
+#[test]
+fn dropoffs_wait_for_every_pickup() {
+  let mut order = order();
+  step(&mut order, &[set_done(FROM, 0, true)]).unwrap();
+
+  assert_eq!(
+    order.check_transition(&set_done(TO, 1, true)),
+    Err(TransitionError::DropoffBeforePickup)
+  );
+}
+
+#[test]
+fn pickups_stay_done_after_a_dropoff() {
+  let mut order = order();
+  step(
+    &mut order,
+    &[
+      set_done(FROM, 0, true),
+      set_done(FROM, 1, true),
+      set_done(TO, 0, true),
+    ],
+  )
+  .unwrap();
+
+  assert_eq!(
+    order.check_transition(&set_done(FROM, 1, false)),
+    Err(TransitionError::PickupAfterDropoff)
+  );
+  let add = Event::AddLeg {
+    guid: 0,
+    destination: FROM,
+  };
+  assert_eq!(
+    order.check_transition(&add),
+    Err(TransitionError::PickupAfterDropoff)
+  );
+  // Dropoffs can still be undone, and added.
+  step(&mut order, &[set_done(TO, 0, false)]).unwrap();
+  let add = Event::AddLeg {
+    guid: 0,
+    destination: TO,
+  };
+  assert_eq!(order.check_transition(&add), Ok(()));
+}
+
+#[test]
+fn only_delivered_orders_complete() {
+  let mut order = order();
+  step(
+    &mut order,
+    &[set_done(FROM, 0, true), set_done(FROM, 1, true)],
+  )
+  .unwrap();
+  let complete = Event::Close {
+    guid: 0,
+    closed: Some(Closed::Completed),
+  };
+
+  assert_eq!(
+    order.check_transition(&complete),
+    Err(TransitionError::NotDelivered(OrderStatus::InTransit))
+  );
+  assert_eq!(delivered().check_transition(&complete), Ok(()));
+}
+
+#[test]
+fn closed_orders_have_to_be_reopened() {
+  let mut order = delivered();
+  step(
+    &mut order,
+    &[Event::Close {
+      guid: 0,
+      closed: Some(Closed::Abandoned),
+    }],
+  )
+  .unwrap();
+
+  let closed = Err(TransitionError::Closed(OrderStatus::Abandoned));
+  for event in [
+    set_done(TO, 0, false),
+    Event::Edit {
+      guid: 0,
+      edit: OrderEdit {
+        notes: Some("late".to_string()),
+        ..Default::default()
+      },
+    },
+    Event::EditLeg {
+      guid: 0,
+      destination: TO,
+      leg: 1,
+      edit: LegEdit {
+        cars: Some(2),
+        ..Default::default()
+      },
+    },
+    Event::AddLeg {
+      guid: 0,
+      destination: TO,
+    },
+    Event::RemoveLeg {
+      guid: 0,
+      destination: TO,
+      leg: 1,
+    },
+    Event::Close {
+      guid: 0,
+      closed: Some(Closed::Completed),
+    },
+    Event::Take {
+      guid: 0,
+      at: Some(1000),
+    },
+    Event::Take { guid: 0, at: None },
+  ] {
+    assert_eq!(order.check_transition(&event), closed, "{event:?}");
+  }
+
+  let reopen = Event::Close {
+    guid: 0,
+    closed: None,
+  };
+  step(&mut order, &[reopen, set_done(TO, 0, false)]).unwrap();
+  assert_eq!(order.status(), OrderStatus::InTransit);
+}
+
+#[test]
+fn lifecycle_of_whole_orders() {
+  let mut order = order();
+  order.to.done = true;
+  assert_eq!(
+    order.check_lifecycle(),
+    Err(TransitionError::DropoffBeforePickup)
+  );
+
+  let mut order = self::order();
+  order.closed = Some(Closed::Completed);
+  assert_eq!(
+    order.check_lifecycle(),
+    Err(TransitionError::NotDelivered(OrderStatus::Accepted))
+  );
+  order.closed = Some(Closed::Abandoned);
+  assert_eq!(order.check_lifecycle(), Ok(()));
+}